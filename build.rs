@@ -1,8 +1,24 @@
 extern crate pkg_config;
 
+/// The oldest hwloc which has `hwloc_get_area_memlocation` and `HWLOC_MEMBIND_BYNODESET`.
+const MIN_VERSION: [u32; 3] = [1, 11, 3];
+
 fn main() {
-    let probed = pkg_config::Config::new().atleast_version("1.11.0").probe("hwloc");
-    if probed.is_ok() {
-        return;
+    // Without pkg-config, hwloc has to be found by the linker on its own, like on Windows.
+    let library = match pkg_config::Config::new().probe("hwloc") {
+        Ok(library) => library,
+        Err(_) => return,
+    };
+
+    let version: Vec<u32> = library.version
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    if version.as_slice() < &MIN_VERSION[..] {
+        panic!("hwloc {} was found, but at least {}.{}.{} is required",
+               library.version,
+               MIN_VERSION[0],
+               MIN_VERSION[1],
+               MIN_VERSION[2]);
     }
 }
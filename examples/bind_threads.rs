@@ -73,7 +73,7 @@ fn main() {
 fn cpuset_for_core(topology: &Topology, idx: usize) -> CpuSet {
     let cores = (*topology).objects_with_type(&ObjectType::Core).unwrap();
     match cores.get(idx) {
-        Some(val) => val.cpuset().unwrap(),
+        Some(val) => val.cpuset().unwrap().clone(),
         None => panic!("No Core found with id {}", idx),
     }
 }
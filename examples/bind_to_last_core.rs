@@ -20,7 +20,7 @@ fn main() {
     let mut topo = Topology::new();
 
     // Grab last core and exctract its CpuSet
-    let mut cpuset = last_core(&mut topo).cpuset().unwrap();
+    let mut cpuset = last_core(&mut topo).cpuset().unwrap().clone();
 
     //  Get only one logical processor (in case the core is SMT/hyper-threaded).
     cpuset.singlify();
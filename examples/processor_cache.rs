@@ -16,8 +16,7 @@ fn main() {
     while let Some(p) = parent {
         if p.object_type() == ObjectType::Cache {
             levels += 1;
-            // This should actually be size(), but there is a (compiler) bug? with the c-ffi unions
-            size += p.cache_attributes().unwrap().size;
+            size += p.cache_attributes().unwrap().size();
         }
         parent = p.parent();
     }
@@ -0,0 +1,523 @@
+//! A NUMA-aware global allocator.
+//!
+//! `NumaAllocator` places allocations on the NUMA node of the allocating thread, or on
+//! an explicit node, without touching the code which allocates. It is enabled with the
+//! `numa-allocator` feature:
+//!
+//! ```
+//! use hwloc::alloc::NumaAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: NumaAllocator = NumaAllocator::new();
+//!
+//! fn main() {
+//!     let buffer = vec![0u8; 1 << 20];
+//!     assert_eq!(1 << 20, buffer.len());
+//! }
+//! ```
+//!
+//! Every NUMA node gets its own arena. Small allocations are served from chunks bound to
+//! the node with `MemBindPolicy::Bind`, large ones are bound one by one. The topology is
+//! loaded on the first allocation; allocations made while it loads, and all allocations on
+//! systems where `TopologyMemBindSupport::alloc()` is false, go to the system allocator.
+//!
+//! With the `allocator-api` feature, which requires a nightly compiler, `NumaAllocator`
+//! also implements the unstable `Allocator` trait.
+
+#[cfg(feature = "allocator-api")]
+use std::alloc::{AllocError, Allocator};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+#[cfg(feature = "allocator-api")]
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use libc::{c_int, c_void};
+
+use bitmap::NodeSet;
+use ffi;
+use {Topology, TopologyBuilder, ObjectType, MemBindPolicy, MEMBIND_BYNODESET};
+
+/// Size of the chunks small allocations are carved from.
+const CHUNK_SIZE: usize = 1 << 20;
+/// The smallest size class, which also holds the free list pointer of a block.
+const MIN_CLASS_SHIFT: usize = 4;
+/// The largest size class, bigger blocks are allocated on their own.
+const MAX_CLASS_SHIFT: usize = 15;
+const CLASSES: usize = MAX_CLASS_SHIFT - MIN_CLASS_SHIFT + 1;
+/// hwloc hands out page-aligned memory, larger alignments go to the system allocator.
+const MAX_ALIGN: usize = 4096;
+
+/// Origin tags stored in the header, any other value is the index of an arena.
+const FROM_SYSTEM: usize = usize::MAX;
+const FROM_HWLOC: usize = usize::MAX - 1;
+
+const UNINIT: u8 = 0;
+const LOADING: u8 = 1;
+const READY: u8 = 2;
+const UNSUPPORTED: u8 = 3;
+
+/// Which NUMA node allocations are placed on.
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    Local,
+    Node(u32),
+}
+
+/// A `GlobalAlloc` placing allocations on NUMA nodes.
+///
+/// See the module documentation for how it works.
+pub struct NumaAllocator {
+    target: Target,
+    state: AtomicU8,
+    inner: UnsafeCell<MaybeUninit<Inner>>,
+}
+
+// The inner state is only written once, before `state` is set to `READY` with release
+// ordering, and the arenas are protected by their own locks.
+unsafe impl Sync for NumaAllocator {}
+unsafe impl Send for NumaAllocator {}
+
+struct Inner {
+    topology: Topology,
+    arenas: Vec<Arena>,
+    /// The arena of every PU, indexed by its OS index.
+    cpu_arenas: Vec<usize>,
+}
+
+/// The memory bound to one NUMA node.
+struct Arena {
+    nodeset: NodeSet,
+    locked: AtomicBool,
+    state: UnsafeCell<ArenaState>,
+}
+
+struct ArenaState {
+    free: [*mut u8; CLASSES],
+    chunk_next: *mut u8,
+    chunk_end: *mut u8,
+    /// The number of chunks installed so far.
+    chunks: usize,
+}
+
+impl NumaAllocator {
+    /// Creates an allocator placing memory on the NUMA node of the allocating thread.
+    pub const fn new() -> NumaAllocator {
+        NumaAllocator::with_target(Target::Local)
+    }
+
+    /// Creates an allocator placing all memory on the NUMA node with the given OS index.
+    ///
+    /// If there is no such node, all memory comes from the system allocator.
+    pub const fn with_node(os_index: u32) -> NumaAllocator {
+        NumaAllocator::with_target(Target::Node(os_index))
+    }
+
+    const fn with_target(target: Target) -> NumaAllocator {
+        NumaAllocator {
+            target,
+            state: AtomicU8::new(UNINIT),
+            inner: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns the loaded state, or `None` if memory has to come from the system allocator.
+    fn inner(&self) -> Option<&Inner> {
+        match self.state.load(Ordering::Acquire) {
+            READY => Some(unsafe { (*self.inner.get()).assume_init_ref() }),
+            UNINIT => {
+                if self.state
+                    .compare_exchange(UNINIT, LOADING, Ordering::Acquire, Ordering::Relaxed)
+                    .is_err() {
+                    return self.inner();
+                }
+                // Loading allocates, which ends up in the system allocator until done.
+                match Inner::load() {
+                    Some(inner) => {
+                        unsafe { (*self.inner.get()).write(inner) };
+                        self.state.store(READY, Ordering::Release);
+                        self.inner()
+                    }
+                    None => {
+                        self.state.store(UNSUPPORTED, Ordering::Release);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn arena(&self, inner: &Inner) -> Option<usize> {
+        match self.target {
+            Target::Local => {
+                let cpu = current_cpu()?;
+                inner.cpu_arenas.get(cpu).cloned()
+            }
+            Target::Node(os_index) => {
+                inner.arenas.iter().position(|arena| arena.nodeset.is_set(os_index))
+            }
+        }
+    }
+}
+
+impl Drop for NumaAllocator {
+    fn drop(&mut self) {
+        // The chunks stay allocated, blocks may still be in use by whoever got them.
+        if *self.state.get_mut() == READY {
+            unsafe { self.inner.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+impl Default for NumaAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inner {
+    fn load() -> Option<Inner> {
+        let topology = TopologyBuilder::new().build().ok()?;
+        if !topology.support().memory().alloc() {
+            return None;
+        }
+
+        let nodes = topology.objects_with_type(&ObjectType::NUMANode).ok()?;
+        if nodes.is_empty() {
+            return None;
+        }
+
+        let mut cpu_arenas = Vec::new();
+        let mut arenas = Vec::with_capacity(nodes.len());
+        for (index, node) in nodes.iter().enumerate() {
+            if let Some(cpuset) = node.cpuset() {
+                for cpu in cpuset.iter() {
+                    if cpu as usize >= cpu_arenas.len() {
+                        cpu_arenas.resize(cpu as usize + 1, 0);
+                    }
+                    cpu_arenas[cpu as usize] = index;
+                }
+            }
+            arenas.push(Arena::new(NodeSet::from(node.os_index())));
+        }
+
+        Some(Inner { topology, arenas, cpu_arenas })
+    }
+
+    /// Allocates `len` bytes bound to the nodes of `arena`.
+    unsafe fn alloc_bound(&self, arena: &Arena, len: usize) -> *mut u8 {
+        ffi::hwloc_alloc_membind(self.topology.topo,
+                                 len,
+                                 arena.nodeset.as_ptr(),
+                                 MemBindPolicy::Bind as c_int,
+                                 MEMBIND_BYNODESET.bits()) as *mut u8
+    }
+}
+
+impl Arena {
+    fn new(nodeset: NodeSet) -> Arena {
+        Arena {
+            nodeset,
+            locked: AtomicBool::new(false),
+            state: UnsafeCell::new(ArenaState {
+                free: [ptr::null_mut(); CLASSES],
+                chunk_next: ptr::null_mut(),
+                chunk_end: ptr::null_mut(),
+                chunks: 0,
+            }),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the arena state.
+    fn with_lock<R, F: FnOnce(&mut ArenaState) -> R>(&self, f: F) -> R {
+        while self.locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err() {
+            std::hint::spin_loop();
+        }
+        let result = f(unsafe { &mut *self.state.get() });
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+}
+
+impl ArenaState {
+    /// Takes a block of `block_size` bytes from the current chunk, or returns null if the
+    /// rest of the chunk is too small.
+    unsafe fn carve(&mut self, block_size: usize) -> *mut u8 {
+        // Blocks start at a multiple of their size, which keeps the data aligned since both
+        // the header and the alignment are at most the block size.
+        let padding = (block_size - self.chunk_next as usize % block_size) % block_size;
+        if (self.chunk_end as usize) - (self.chunk_next as usize) < padding + block_size {
+            return ptr::null_mut();
+        }
+        let block = self.chunk_next.add(padding);
+        self.chunk_next = block.add(block_size);
+        block
+    }
+}
+
+/// The size class of a block of `size` bytes, or `None` if it is too large for one.
+fn size_class(size: usize) -> Option<usize> {
+    let shift = size.next_power_of_two().trailing_zeros() as usize;
+    if shift > MAX_CLASS_SHIFT {
+        None
+    } else {
+        Some(shift.max(MIN_CLASS_SHIFT) - MIN_CLASS_SHIFT)
+    }
+}
+
+/// The header in front of every allocation, large enough to keep the data aligned.
+fn header_size(layout: &Layout) -> usize {
+    layout.align().max(16)
+}
+
+#[cfg(target_os = "linux")]
+fn current_cpu() -> Option<usize> {
+    match unsafe { libc::sched_getcpu() } {
+        cpu if cpu < 0 => None,
+        cpu => Some(cpu as usize),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn current_cpu() -> Option<usize> {
+    Some(0)
+}
+
+/// Writes the origin `tag` in front of the data of a block starting at `base`.
+unsafe fn finish(base: *mut u8, header: usize, tag: usize) -> *mut u8 {
+    let data = base.add(header);
+    (data as *mut usize).sub(1).write(tag);
+    data
+}
+
+unsafe impl GlobalAlloc for NumaAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let header = header_size(&layout);
+        let size = match layout.size().checked_add(header) {
+            Some(size) => size,
+            None => return ptr::null_mut(),
+        };
+
+        let placed = match self.inner() {
+            Some(inner) if layout.align() <= MAX_ALIGN => {
+                self.arena(inner).map(|index| (inner, index))
+            }
+            _ => None,
+        };
+        let (inner, index) = match placed {
+            Some(placed) => placed,
+            None => {
+                let base = System.alloc(Layout::from_size_align_unchecked(size, header));
+                return if base.is_null() { base } else { finish(base, header, FROM_SYSTEM) };
+            }
+        };
+
+        let arena = &inner.arenas[index];
+        let class = match size_class(size) {
+            Some(class) => class,
+            None => {
+                let base = inner.alloc_bound(arena, size);
+                return if base.is_null() { base } else { finish(base, header, FROM_HWLOC) };
+            }
+        };
+
+        let block_size = 1 << (class + MIN_CLASS_SHIFT);
+        // Chunks are allocated without holding the lock. If another thread refilled the
+        // arena in the meantime, the spare chunk goes back to hwloc.
+        let mut chunk: *mut u8 = ptr::null_mut();
+        loop {
+            let (base, installed) = arena.with_lock(|state| {
+                let block = state.free[class];
+                if !block.is_null() {
+                    state.free[class] = *(block as *mut *mut u8);
+                    return (block, false);
+                }
+
+                let block = state.carve(block_size);
+                if !block.is_null() || chunk.is_null() {
+                    return (block, false);
+                }
+                // The rest of the current chunk is given up, it's smaller than a block.
+                state.chunk_next = chunk;
+                state.chunk_end = chunk.add(CHUNK_SIZE);
+                state.chunks += 1;
+                (state.carve(block_size), true)
+            });
+            if !chunk.is_null() && !installed {
+                ffi::hwloc_free(inner.topology.topo, chunk as *mut c_void, CHUNK_SIZE);
+            }
+            if !base.is_null() {
+                return finish(base, header, index);
+            }
+            if !chunk.is_null() {
+                return ptr::null_mut();
+            }
+
+            chunk = inner.alloc_bound(arena, CHUNK_SIZE);
+            if chunk.is_null() {
+                return chunk;
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, data: *mut u8, layout: Layout) {
+        let header = header_size(&layout);
+        let size = layout.size() + header;
+        let base = data.sub(header);
+
+        match (data as *mut usize).sub(1).read() {
+            FROM_SYSTEM => System.dealloc(base, Layout::from_size_align_unchecked(size, header)),
+            tag => {
+                // Only allocations from the arenas carry other tags, so the state is loaded.
+                let inner = (*self.inner.get()).assume_init_ref();
+                match size_class(size) {
+                    Some(class) if tag != FROM_HWLOC => {
+                        inner.arenas[tag].with_lock(|state| {
+                            *(base as *mut *mut u8) = state.free[class];
+                            state.free[class] = base;
+                        })
+                    }
+                    _ => {
+                        ffi::hwloc_free(inner.topology.topo, base as *mut c_void, size);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "allocator-api")]
+unsafe impl Allocator for NumaAllocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            let dangling = unsafe { NonNull::new_unchecked(layout.align() as *mut u8) };
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+
+        let data = unsafe { self.alloc(layout) };
+        match NonNull::new(data) {
+            Some(data) => Ok(NonNull::slice_from_raw_parts(data, layout.size())),
+            None => Err(AllocError),
+        }
+    }
+
+    unsafe fn deallocate(&self, data: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            self.dealloc(data.as_ptr(), layout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Allocates and fills blocks of various sizes and alignments, then frees them.
+    fn exercise(allocator: &NumaAllocator) {
+        let layouts = [(1, 1), (24, 8), (100, 16), (4000, 64), (32768, 8), (100000, 4096),
+                       (64, 8192)];
+        for _ in 0..3 {
+            let mut blocks = Vec::new();
+            for &(size, align) in layouts.iter() {
+                let layout = Layout::from_size_align(size, align).unwrap();
+                let data = unsafe { allocator.alloc(layout) };
+                assert!(!data.is_null());
+                assert_eq!(0, data as usize % align);
+                unsafe { ptr::write_bytes(data, 0xab, size) };
+                blocks.push((data, layout));
+            }
+            for (data, layout) in blocks {
+                assert_eq!(0xab, unsafe { *data.add(layout.size() - 1) });
+                unsafe { allocator.dealloc(data, layout) };
+            }
+        }
+    }
+
+    #[test]
+    fn should_allocate_on_local_node() {
+        exercise(&NumaAllocator::new());
+    }
+
+    #[test]
+    fn should_align_interleaved_size_classes() {
+        let allocator = NumaAllocator::new();
+        let layouts = [(1, 1), (24, 8), (100, 16), (4000, 64), (40, 32), (300, 128), (8, 256),
+                       (2000, 1024)];
+        let mut blocks = Vec::new();
+        for _ in 0..50 {
+            for &(size, align) in layouts.iter() {
+                let layout = Layout::from_size_align(size, align).unwrap();
+                let data = unsafe { allocator.alloc(layout) };
+                assert!(!data.is_null());
+                assert_eq!(0, data as usize % align, "{:?}", layout);
+                blocks.push((data, layout));
+            }
+        }
+        for (data, layout) in blocks {
+            unsafe { allocator.dealloc(data, layout) };
+        }
+    }
+
+    #[test]
+    fn should_not_lose_chunks_under_contention() {
+        const THREADS: usize = 8;
+        const BLOCKS: usize = 40000;
+        // 48 bytes and the 16 byte header fill a 64 byte block.
+        let layout = Layout::from_size_align(48, 8).unwrap();
+
+        let allocator = Arc::new(NumaAllocator::new());
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let allocator = allocator.clone();
+                thread::spawn(move || {
+                    (0..BLOCKS)
+                        .map(|_| unsafe { allocator.alloc(layout) } as usize)
+                        .collect::<Vec<usize>>()
+                })
+            })
+            .collect();
+        let blocks: Vec<usize> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        assert!(blocks.iter().all(|&block| block != 0));
+
+        if let Some(inner) = allocator.inner() {
+            // Every arena wastes less than a block per chunk, so it needs at most one
+            // chunk more than its share of the blocks fills.
+            let chunks: usize =
+                inner.arenas.iter().map(|arena| arena.with_lock(|state| state.chunks)).sum();
+            let needed = THREADS * BLOCKS * 64 / CHUNK_SIZE + inner.arenas.len();
+            assert!(chunks <= needed, "{} chunks installed, {} needed", chunks, needed);
+        }
+
+        for block in blocks {
+            unsafe { allocator.dealloc(block as *mut u8, layout) };
+        }
+    }
+
+    #[test]
+    fn should_fall_back_for_missing_node() {
+        let allocator = NumaAllocator::with_node(u32::MAX);
+        exercise(&allocator);
+    }
+
+    #[test]
+    fn should_allocate_from_many_threads() {
+        let allocator = Arc::new(NumaAllocator::with_node(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let allocator = allocator.clone();
+                thread::spawn(move || exercise(&allocator))
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
@@ -1,31 +1,132 @@
 use ffi;
-use libc::c_char;
+use div_round_up;
+use libc::{c_char, c_int, c_uint, c_ulong};
 use std::{fmt, ptr};
-use std::ffi::CStr;
-use std::ops::Not;
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::str::FromStr;
+use std::ops::{Not, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Sub,
+               SubAssign};
+use std::cmp::Ordering;
 use std::clone::Clone;
+use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
 
 pub enum IntHwlocBitmap {}
 
 /// A generic bitmap, understood by hwloc.
 ///
 /// The `Bitmap` represents a set of objects, typically OS processors – which may actually be
-/// hardware threads (represented by `CpuSet`, which is a type alias for `Bitmap` – or memory
-/// nodes (represented by `NodeSet`, which is also a typedef for `Bitmap`).
+/// hardware threads (represented by `CpuSet`, which wraps a `Bitmap`) – or memory
+/// nodes (represented by `NodeSet`, which also wraps a `Bitmap`).
 ///
 /// Both `CpuSet` and `NodeSet` are always indexed by OS physical number.
 ///
 /// A `Bitmap` may be of infinite size.
+///
+/// A `Bitmap` always owns its hwloc bitmap and frees it when dropped. Sets which are
+/// owned by a `Topology`, like the CPU set of a `TopologyObject`, are handed out as
+/// read-only `BitmapRef`s instead.
 pub struct Bitmap {
     bitmap: *mut IntHwlocBitmap,
-    manage: bool,
+}
+
+/// A read-only `Bitmap`, `CpuSet` or `NodeSet` borrowed from a `Topology`.
+///
+/// The sets of a `TopologyObject` are stored inside the topology, so they can
+/// neither be modified nor outlive it. A `BitmapRef` dereferences to the set type `T`
+/// for all read-only operations, use `clone()` to get an owned and modifiable copy.
+///
+/// Examples:
+///
+/// ```
+/// use hwloc::{Topology, CpuSet};
+///
+/// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+/// let cpuset = topology.object_at_root().cpuset().unwrap();
+/// assert_eq!(8, cpuset.weight());
+///
+/// let mut owned: CpuSet = cpuset.clone();
+/// owned.singlify();
+/// assert_eq!(1, owned.weight());
+/// ```
+///
+/// Modifying a borrowed set does not compile:
+///
+/// ```compile_fail
+/// use hwloc::Topology;
+///
+/// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+/// let mut cpuset = topology.object_at_root().cpuset().unwrap();
+/// cpuset.singlify();
+/// ```
+///
+/// And neither does keeping it around longer than the topology:
+///
+/// ```compile_fail
+/// use hwloc::Topology;
+///
+/// let cpuset = {
+///     let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+///     topology.object_at_root().cpuset().unwrap()
+/// };
+/// ```
+pub struct BitmapRef<'a, T = Bitmap> {
+    bitmap: ManuallyDrop<T>,
+    marker: PhantomData<&'a T>,
 }
 
 /// A `CpuSet` is a `Bitmap` whose bits are set according to CPU physical OS indexes.
-pub type CpuSet = Bitmap;
+///
+/// `CpuSet` and `NodeSet` are distinct types, so one cannot be passed where the other
+/// is expected by accident. Use `Topology::cpuset_to_nodeset` and
+/// `Topology::nodeset_to_cpuset` to convert between them. A `CpuSet` dereferences to
+/// `Bitmap` for all set operations, except that the ones relating two sets, like
+/// `intersects`, only take another `CpuSet`.
+///
+/// Examples:
+///
+/// ```
+/// use hwloc::{Bitmap, CpuSet};
+///
+/// let mut cpuset = CpuSet::from_range(0, 3);
+/// cpuset.set(8);
+/// assert_eq!("0-3,8", cpuset.to_string());
+///
+/// let bitmap: Bitmap = cpuset.into();
+/// let cpuset = CpuSet::from_bitmap(bitmap);
+/// assert_eq!(5, cpuset.weight());
+/// ```
+///
+/// Passing a `NodeSet` instead does not compile:
+///
+/// ```compile_fail
+/// use hwloc::{Topology, NodeSet};
+///
+/// let topology = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+/// topology.object_covering_cpuset(&NodeSet::from(0));
+/// ```
+///
+/// Neither does relating it to a `CpuSet`:
+///
+/// ```compile_fail
+/// use hwloc::{CpuSet, NodeSet};
+///
+/// CpuSet::from(0).intersects(&NodeSet::from(0));
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct CpuSet(Bitmap);
+
 /// A `NodeSet` is a `Bitmap` whose bits are set according to NUMA memory node physical OS indexes.
-pub type NodeSet = Bitmap;
+///
+/// See `CpuSet` for how the typed sets relate to `Bitmap`.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct NodeSet(Bitmap);
 
 impl Bitmap {
     /// Creates an empty `Bitmap`.
@@ -41,10 +142,7 @@ impl Bitmap {
     // ```
     pub fn new() -> Bitmap {
         let bitmap = unsafe { ffi::hwloc_bitmap_alloc() };
-        Bitmap {
-            bitmap,
-            manage: true,
-        }
+        Bitmap { bitmap }
     }
 
     /// Creates a full `Bitmap`.
@@ -60,10 +158,7 @@ impl Bitmap {
     // ```
     pub fn full() -> Bitmap {
         let bitmap = unsafe { ffi::hwloc_bitmap_alloc_full() };
-        Bitmap {
-            bitmap,
-            manage: true,
-        }
+        Bitmap { bitmap }
     }
 
     /// Creates a new HwlocBitmap (either CpuSet or NodeSet) and sets one index right away.
@@ -99,15 +194,106 @@ impl Bitmap {
         bitmap
     }
 
+    /// Parses a `Bitmap` from the list format, like `"0-3,8,12-"`.
+    ///
+    /// This is the format used by `Display` and by Linux in files like
+    /// `/sys/devices/system/cpu/online`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_list_str("0-3,8").unwrap();
+    /// assert_eq!(5, bitmap.weight());
+    /// assert!(bitmap.is_set(8));
+    /// ```
+    pub fn from_list_str(s: &str) -> Result<Bitmap, ParseBitmapError> {
+        Bitmap::parse(s, ffi::hwloc_bitmap_list_sscanf)
+    }
+
+    /// Parses a `Bitmap` from the hwloc hexadecimal format, like `"0x0000000f,0x00000001"`.
+    ///
+    /// The string is made of comma-separated 32-bit hexadecimal words, most
+    /// significant first. A leading `0xf...f,` denotes an infinitely set tail.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_hex_str("0x00000001,0x0000000f").unwrap();
+    /// assert_eq!("0-3,32", format!("{}", bitmap));
+    /// ```
+    pub fn from_hex_str(s: &str) -> Result<Bitmap, ParseBitmapError> {
+        Bitmap::parse(s, ffi::hwloc_bitmap_sscanf)
+    }
+
+    /// Parses a `Bitmap` from the format used by the `taskset` program, like `"0xf0"`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_taskset_str("0xf0").unwrap();
+    /// assert_eq!("4-7", format!("{}", bitmap));
+    /// ```
+    pub fn from_taskset_str(s: &str) -> Result<Bitmap, ParseBitmapError> {
+        Bitmap::parse(s, ffi::hwloc_bitmap_taskset_sscanf)
+    }
+
+    /// Creates a `Bitmap` from an array of `unsigned long` masks, least significant first.
+    ///
+    /// Index `i` is bit `i % c_ulong::BITS` of `ulongs[i / c_ulong::BITS]`, which is also the
+    /// layout of `libc::cpu_set_t` and of the dynamically sized masks of glibc's `CPU_ALLOC`.
+    /// Each mask is stored with `hwloc_bitmap_set_ith_ulong`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_ulongs(&[0xf, 0x1]);
+    /// assert!(bitmap.is_set(3));
+    /// assert_eq!(5, bitmap.weight());
+    /// ```
+    pub fn from_ulongs(ulongs: &[c_ulong]) -> Bitmap {
+        let bitmap = Bitmap::new();
+        for (i, &ulong) in ulongs.iter().enumerate() {
+            unsafe { ffi::hwloc_bitmap_set_ith_ulong(bitmap.bitmap, i as c_uint, ulong) }
+        }
+        bitmap
+    }
+
+    fn parse(s: &str,
+             sscanf: unsafe extern "C" fn(*mut IntHwlocBitmap, *const c_char) -> c_int)
+             -> Result<Bitmap, ParseBitmapError> {
+        let input = match CString::new(s) {
+            Ok(input) => input,
+            Err(_) => return Err(ParseBitmapError::new(s)),
+        };
+
+        let bitmap = Bitmap::new();
+        let result = unsafe { sscanf(bitmap.bitmap, input.as_ptr()) };
+        if result < 0 {
+            Err(ParseBitmapError::new(s))
+        } else {
+            Ok(bitmap)
+        }
+    }
+
     /// Wraps the given hwloc bitmap pointer into its `Bitmap` representation.
     ///
     /// This function is not meant to be used directly, it rather serves as the
     /// conversion factory when dealing with hwloc-internal structures.
-    pub fn from_raw(bitmap: *mut IntHwlocBitmap, manage: bool) -> Bitmap {
-        Bitmap {
-            bitmap,
-            manage,
-        }
+    ///
+    /// # Safety
+    ///
+    /// The pointer must come from `hwloc_bitmap_alloc` or a similar function and
+    /// must not be owned by anything else, since the `Bitmap` frees it on drop.
+    pub unsafe fn from_raw(bitmap: *mut IntHwlocBitmap) -> Bitmap {
+        Bitmap { bitmap }
     }
 
     /// Returns the containted hwloc bitmap pointer for interaction with hwloc.
@@ -115,6 +301,11 @@ impl Bitmap {
         self.bitmap as *const IntHwlocBitmap
     }
 
+    /// Returns the contained hwloc bitmap pointer for modification through hwloc.
+    pub fn as_mut_ptr(&mut self) -> *mut IntHwlocBitmap {
+        self.bitmap
+    }
+
     /// Set index `id` in this `Bitmap`.
     ///
     /// Examples:
@@ -352,115 +543,1032 @@ impl Bitmap {
         let result = unsafe { ffi::hwloc_bitmap_isfull(self.bitmap) };
         result == 1
     }
-}
 
-impl Not for Bitmap {
-    type Output = Bitmap;
-
-    /// Returns a new bitmap which contains the negated values of the current
-    /// one.
-    fn not(self) -> Bitmap {
-        unsafe {
-            let result = ffi::hwloc_bitmap_alloc();
-            ffi::hwloc_bitmap_not(result, self.bitmap);
-            Bitmap::from_raw(result, true)
+    /// Iterates over the set indexes of this `Bitmap` in increasing order, without consuming it.
+    ///
+    /// Note that the iterator never ends if the `Bitmap` is infinitely set. For the same
+    /// reason `BitmapIter` does not implement `ExactSizeIterator`, use `try_iter` for that.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(2, 4);
+    /// assert_eq!(vec![2, 3, 4], bitmap.iter().collect::<Vec<u32>>());
+    /// assert_eq!(3, bitmap.weight());
+    /// ```
+    pub fn iter(&self) -> BitmapIter<'_> {
+        let weight = self.weight();
+        BitmapIter {
+            bitmap: self,
+            index: -1,
+            remaining: if weight < 0 { None } else { Some(weight as usize) },
         }
     }
-}
 
-impl Drop for Bitmap {
-    fn drop(&mut self) {
-        if self.manage {
-            unsafe { ffi::hwloc_bitmap_free(self.bitmap) }
+    /// Iterates over the set indexes of this `Bitmap` in increasing order, or returns `None`
+    /// if the `Bitmap` is infinitely set.
+    ///
+    /// Unlike `iter`, the returned iterator implements `ExactSizeIterator`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(2, 4);
+    /// assert_eq!(3, bitmap.try_iter().unwrap().len());
+    /// assert!(Bitmap::full().try_iter().is_none());
+    /// ```
+    pub fn try_iter(&self) -> Option<BitmapFiniteIter<'_>> {
+        let weight = self.weight();
+        if weight < 0 {
+            return None;
         }
+
+        Some(BitmapFiniteIter {
+            inner: self.iter(),
+            remaining: weight as usize,
+        })
     }
-}
 
-impl fmt::Display for Bitmap {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut result: *mut c_char = ptr::null_mut();
-        unsafe {
-            ffi::hwloc_bitmap_list_asprintf(&mut result, self.bitmap);
-            let res = write!(f, "{}", CStr::from_ptr(result).to_str().unwrap());
-            libc::free(result as *mut libc::c_void);
-            res
+    /// Iterates over the set indexes of this `Bitmap` in decreasing order.
+    ///
+    /// The iterator works on a copy of the `Bitmap`, so it does not borrow it.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let mut bitmap = Bitmap::from_range(2, 4);
+    /// bitmap.set(8);
+    /// assert_eq!(vec![8, 4, 3, 2], bitmap.iter_rev().collect::<Vec<u32>>());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the `Bitmap` is infinitely set, since there is no
+    /// last index to start from.
+    pub fn iter_rev(&self) -> BitmapRevIter {
+        let weight = self.weight();
+        if weight < 0 {
+            panic!("Can't iterate an infinite bitmap in reverse order.");
+        }
+
+        BitmapRevIter {
+            remaining_bits: self.clone(),
+            remaining: weight as usize,
         }
     }
-}
 
-impl fmt::Debug for Bitmap {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut result: *mut c_char = ptr::null_mut();
-        unsafe {
-            ffi::hwloc_bitmap_list_asprintf(&mut result, self.bitmap);
-            let res = write!(f, "{}", CStr::from_ptr(result).to_str().unwrap());
-            libc::free(result as *mut libc::c_void);
-            res
+    /// Iterates over the ranges of consecutive set indexes in this `Bitmap`.
+    ///
+    /// Each range is returned as a `(begin, end)` tuple with an inclusive `end`,
+    /// which is -1 if the range is infinite. This matches the arguments of
+    /// `set_range` and `from_range`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let mut bitmap = Bitmap::from_range(0, 3);
+    /// bitmap.set_range(8, 11);
+    /// bitmap.set_range(16, -1);
+    /// assert_eq!(vec![(0, 3), (8, 11), (16, -1)], bitmap.ranges().collect::<Vec<_>>());
+    /// ```
+    pub fn ranges(&self) -> BitmapRanges<'_> {
+        let infinite_from = if self.weight() < 0 {
+            // The inverse of an infinite bitmap is finite, its last index is
+            // the last one before the infinitely set tail.
+            Some((!self.clone()).last() + 1)
+        } else {
+            None
+        };
+
+        BitmapRanges {
+            bitmap: self,
+            next: self.first(),
+            infinite_from,
         }
     }
-}
 
-impl Clone for Bitmap {
-    fn clone(&self) -> Bitmap {
-        let dup = unsafe { ffi::hwloc_bitmap_dup(self.bitmap) };
-        Bitmap::from_raw(dup, true)
+    /// Iterates over the indexes below `end` which are not set in this `Bitmap`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(2, 4);
+    /// let unset = bitmap.iter_unset(7);
+    /// assert_eq!(4, unset.len());
+    /// assert_eq!(vec![0, 1, 5, 6], unset.collect::<Vec<u32>>());
+    /// ```
+    pub fn iter_unset(&self, end: u32) -> BitmapUnsetIter {
+        let mut unset = !self.clone();
+        unset.unset_range(end, -1);
+        let remaining = unset.weight() as usize;
+
+        BitmapUnsetIter {
+            inner: unset.into_iter(),
+            remaining,
+        }
     }
-}
 
-impl PartialEq for Bitmap {
-    fn eq(&self, other: &Self) -> bool {
-        let result = unsafe { ffi::hwloc_bitmap_isequal(self.bitmap, other.as_ptr()) };
+    /// Test whether this `Bitmap` and `other` have at least one index in common.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(0, 3);
+    /// assert!(bitmap.intersects(&Bitmap::from_range(3, 5)));
+    /// assert!(!bitmap.intersects(&Bitmap::from_range(4, 5)));
+    /// ```
+    pub fn intersects(&self, other: &Bitmap) -> bool {
+        let result = unsafe { ffi::hwloc_bitmap_intersects(self.bitmap, other.bitmap) };
         result == 1
     }
-}
 
-impl IntoIterator for Bitmap {
-    type Item = u32;
-    type IntoIter = BitmapIntoIterator;
+    /// Test whether all indexes of this `Bitmap` are also set in `other`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(1, 2);
+    /// assert!(bitmap.is_included_in(&Bitmap::from_range(0, 3)));
+    /// assert!(!bitmap.is_included_in(&Bitmap::from_range(2, 3)));
+    /// ```
+    pub fn is_included_in(&self, other: &Bitmap) -> bool {
+        let result = unsafe { ffi::hwloc_bitmap_isincluded(self.bitmap, other.bitmap) };
+        result == 1
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        BitmapIntoIterator {
-            bitmap: self,
-            index: -1,
+    /// Returns a new `Bitmap` with the indexes of this one which are not set in `other`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(0, 5);
+    /// assert_eq!("0-1,5", format!("{}", bitmap.andnot(&Bitmap::from_range(2, 4))));
+    /// ```
+    pub fn andnot(&self, other: &Bitmap) -> Bitmap {
+        unsafe {
+            let result = ffi::hwloc_bitmap_alloc();
+            ffi::hwloc_bitmap_andnot(result, self.bitmap, other.bitmap);
+            Bitmap::from_raw(result)
         }
     }
-}
-
-pub struct BitmapIntoIterator {
-    bitmap: Bitmap,
-    index: i32,
-}
 
-impl Iterator for BitmapIntoIterator {
-    type Item = u32;
+    /// Returns a new `Bitmap` with the indexes set in both this one and `other`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(0, 5);
+    /// assert_eq!("3-5", format!("{}", bitmap.and(&Bitmap::from_range(3, 8))));
+    /// ```
+    pub fn and(&self, other: &Bitmap) -> Bitmap {
+        unsafe {
+            let result = ffi::hwloc_bitmap_alloc();
+            ffi::hwloc_bitmap_and(result, self.bitmap, other.bitmap);
+            Bitmap::from_raw(result)
+        }
+    }
 
-    fn next(&mut self) -> Option<u32> {
-        let result = unsafe { ffi::hwloc_bitmap_next(self.bitmap.as_ptr(), self.index) };
-        self.index = result;
-        if result < 0 {
-            None
-        } else {
-            Some(result as u32)
+    /// Returns a new `Bitmap` with the indexes set in this one, `other` or both.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(0, 2);
+    /// assert_eq!("0-2,5-6", format!("{}", bitmap.or(&Bitmap::from_range(5, 6))));
+    /// ```
+    pub fn or(&self, other: &Bitmap) -> Bitmap {
+        unsafe {
+            let result = ffi::hwloc_bitmap_alloc();
+            ffi::hwloc_bitmap_or(result, self.bitmap, other.bitmap);
+            Bitmap::from_raw(result)
         }
     }
-}
 
-impl FromIterator<u32> for Bitmap {
-    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Bitmap {
-        let mut bitmap = Bitmap::new();
-        for i in iter {
-            bitmap.set(i);
+    /// Returns a new `Bitmap` with the indexes set in either this one or `other`, but not both.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let bitmap = Bitmap::from_range(0, 5);
+    /// assert_eq!("0-2,6-8", format!("{}", bitmap.xor(&Bitmap::from_range(3, 8))));
+    /// ```
+    pub fn xor(&self, other: &Bitmap) -> Bitmap {
+        unsafe {
+            let result = ffi::hwloc_bitmap_alloc();
+            ffi::hwloc_bitmap_xor(result, self.bitmap, other.bitmap);
+            Bitmap::from_raw(result)
         }
-        bitmap
     }
-}
 
-impl Default for Bitmap {
-    fn default() -> Self {
+    /// Compares this `Bitmap` with `other` using their lowest index.
+    ///
+    /// The bitmap with the smaller least significant bit is smaller. An empty
+    /// `Bitmap` is considered greater than any non-empty one.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    /// use std::cmp::Ordering;
+    ///
+    /// let bitmap = Bitmap::from_range(2, 3);
+    /// assert_eq!(Ordering::Less, bitmap.compare_first(&Bitmap::from(4)));
+    /// assert_eq!(Ordering::Equal, bitmap.compare_first(&Bitmap::from_range(2, 8)));
+    /// assert_eq!(Ordering::Less, bitmap.compare_first(&Bitmap::new()));
+    /// ```
+    pub fn compare_first(&self, other: &Bitmap) -> Ordering {
+        let result = unsafe { ffi::hwloc_bitmap_compare_first(self.bitmap, other.bitmap) };
+        result.cmp(&0)
+    }
+
+    /// Formats this `Bitmap` in the list format, like `"0-3,8,12-"`.
+    ///
+    /// This is the same as the output of `Display`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// let mut bitmap = Bitmap::from_range(0, 3);
+    /// bitmap.set_range(8, -1);
+    /// assert_eq!("0-3,8-", bitmap.to_list_string());
+    /// ```
+    pub fn to_list_string(&self) -> String {
+        self.format(ffi::hwloc_bitmap_list_asprintf)
+    }
+
+    /// Formats this `Bitmap` in the hwloc hexadecimal format, like `"0x0000000f,0x00000001"`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// assert_eq!("0x0000000f", Bitmap::from_range(0, 3).to_hex_string());
+    /// ```
+    pub fn to_hex_string(&self) -> String {
+        self.format(ffi::hwloc_bitmap_asprintf)
+    }
+
+    /// Formats this `Bitmap` in the format used by the `taskset` program, like `"0xf0"`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::Bitmap;
+    ///
+    /// assert_eq!("0xf0", Bitmap::from_range(4, 7).to_taskset_string());
+    /// ```
+    pub fn to_taskset_string(&self) -> String {
+        self.format(ffi::hwloc_bitmap_taskset_asprintf)
+    }
+
+    /// Returns the indexes of this `Bitmap` as an array of `unsigned long` masks.
+    ///
+    /// The array is as short as possible and uses the layout described in `from_ulongs`,
+    /// so it can be passed to `sched_setaffinity` for any number of CPUs. Each mask is read
+    /// with `hwloc_bitmap_to_ith_ulong`.
+    ///
+    /// Fails with `BitmapConversionError::Infinite` if the bitmap is infinite.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::{Bitmap, BitmapConversionError};
+    ///
+    /// assert_eq!(Ok(vec![0xf0]), Bitmap::from_range(4, 7).to_ulongs());
+    /// assert_eq!(Ok(vec![]), Bitmap::new().to_ulongs());
+    /// assert_eq!(Err(BitmapConversionError::Infinite), Bitmap::full().to_ulongs());
+    /// ```
+    pub fn to_ulongs(&self) -> Result<Vec<c_ulong>, BitmapConversionError> {
+        if self.weight() < 0 {
+            return Err(BitmapConversionError::Infinite);
+        }
+
+        let ulongs = div_round_up((self.last() + 1) as usize, mem::size_of::<c_ulong>() * 8);
+        Ok((0..ulongs)
+            .map(|i| unsafe { ffi::hwloc_bitmap_to_ith_ulong(self.bitmap, i as c_uint) })
+            .collect())
+    }
+
+    fn format(&self,
+              asprintf: unsafe extern "C" fn(*mut *mut c_char, *const IntHwlocBitmap) -> c_int)
+              -> String {
+        let mut result: *mut c_char = ptr::null_mut();
+        unsafe {
+            asprintf(&mut result, self.bitmap);
+            let formatted = CStr::from_ptr(result).to_str().unwrap().to_string();
+            libc::free(result as *mut libc::c_void);
+            formatted
+        }
+    }
+}
+
+impl Not for Bitmap {
+    type Output = Bitmap;
+
+    /// Returns a new bitmap which contains the negated values of the current
+    /// one.
+    fn not(self) -> Bitmap {
+        unsafe {
+            let result = ffi::hwloc_bitmap_alloc();
+            ffi::hwloc_bitmap_not(result, self.bitmap);
+            Bitmap::from_raw(result)
+        }
+    }
+}
+
+/// Implements a binary set operator for owned and borrowed `Bitmap`s, together
+/// with its assigning variant, on top of the given hwloc function.
+macro_rules! impl_bitmap_op {
+    ($op:ident, $method:ident, $assign_op:ident, $assign_method:ident, $ffi:ident) => {
+        impl<'a, 'b> $op<&'b Bitmap> for &'a Bitmap {
+            type Output = Bitmap;
+
+            fn $method(self, rhs: &'b Bitmap) -> Bitmap {
+                unsafe {
+                    let result = ffi::hwloc_bitmap_alloc();
+                    ffi::$ffi(result, self.bitmap, rhs.bitmap);
+                    Bitmap::from_raw(result)
+                }
+            }
+        }
+
+        impl<'b> $op<&'b Bitmap> for Bitmap {
+            type Output = Bitmap;
+
+            fn $method(self, rhs: &'b Bitmap) -> Bitmap {
+                (&self).$method(rhs)
+            }
+        }
+
+        impl<'a> $op<Bitmap> for &'a Bitmap {
+            type Output = Bitmap;
+
+            fn $method(self, rhs: Bitmap) -> Bitmap {
+                self.$method(&rhs)
+            }
+        }
+
+        impl $op<Bitmap> for Bitmap {
+            type Output = Bitmap;
+
+            fn $method(self, rhs: Bitmap) -> Bitmap {
+                (&self).$method(&rhs)
+            }
+        }
+
+        impl<'b> $assign_op<&'b Bitmap> for Bitmap {
+            fn $assign_method(&mut self, rhs: &'b Bitmap) {
+                unsafe { ffi::$ffi(self.bitmap, self.bitmap, rhs.bitmap) }
+            }
+        }
+
+        impl $assign_op<Bitmap> for Bitmap {
+            fn $assign_method(&mut self, rhs: Bitmap) {
+                self.$assign_method(&rhs)
+            }
+        }
+    }
+}
+
+impl_bitmap_op!(BitAnd, bitand, BitAndAssign, bitand_assign, hwloc_bitmap_and);
+impl_bitmap_op!(BitOr, bitor, BitOrAssign, bitor_assign, hwloc_bitmap_or);
+impl_bitmap_op!(BitXor, bitxor, BitXorAssign, bitxor_assign, hwloc_bitmap_xor);
+impl_bitmap_op!(Sub, sub, SubAssign, sub_assign, hwloc_bitmap_andnot);
+
+impl Drop for Bitmap {
+    fn drop(&mut self) {
+        unsafe { ffi::hwloc_bitmap_free(self.bitmap) }
+    }
+}
+
+impl<'a, T: From<Bitmap>> BitmapRef<'a, T> {
+    /// Wraps the given hwloc bitmap pointer, owned by a topology, into a `BitmapRef`.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must not be null and must stay valid and unmodified for the
+    /// lifetime `'a`.
+    pub unsafe fn from_raw(bitmap: *const IntHwlocBitmap) -> BitmapRef<'a, T> {
+        let bitmap = Bitmap { bitmap: bitmap as *mut IntHwlocBitmap };
+        BitmapRef {
+            bitmap: ManuallyDrop::new(T::from(bitmap)),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Deref for BitmapRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.bitmap
+    }
+}
+
+impl<'a, T: fmt::Display> fmt::Display for BitmapRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for BitmapRef<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, 'b, T: PartialEq> PartialEq<BitmapRef<'b, T>> for BitmapRef<'a, T> {
+    fn eq(&self, other: &BitmapRef<'b, T>) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a, T: Eq> Eq for BitmapRef<'a, T> {}
+
+impl<'a, T: Hash> Hash for BitmapRef<'a, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<'a, T: PartialEq> PartialEq<T> for BitmapRef<'a, T> {
+    fn eq(&self, other: &T) -> bool {
+        **self == *other
+    }
+}
+
+impl<'a, 'b, T: AsRef<Bitmap>> IntoIterator for &'b BitmapRef<'a, T> {
+    type Item = u32;
+    type IntoIter = BitmapIter<'b>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        (*self.bitmap).as_ref().iter()
+    }
+}
+
+impl AsRef<Bitmap> for Bitmap {
+    fn as_ref(&self) -> &Bitmap {
+        self
+    }
+}
+
+/// Implements comparison of an owned set with a `BitmapRef` borrowing the same kind of set.
+macro_rules! impl_eq_bitmap_ref {
+    ($set:ident) => {
+        impl<'a> PartialEq<BitmapRef<'a, $set>> for $set {
+            fn eq(&self, other: &BitmapRef<'a, $set>) -> bool {
+                *self == **other
+            }
+        }
+    }
+}
+
+impl_eq_bitmap_ref!(Bitmap);
+impl_eq_bitmap_ref!(CpuSet);
+impl_eq_bitmap_ref!(NodeSet);
+
+impl fmt::Display for Bitmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_list_string())
+    }
+}
+
+impl fmt::Debug for Bitmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_list_string())
+    }
+}
+
+impl FromStr for Bitmap {
+    type Err = ParseBitmapError;
+
+    /// Parses a `Bitmap` from the list format, see `Bitmap::from_list_str`.
+    fn from_str(s: &str) -> Result<Bitmap, ParseBitmapError> {
+        Bitmap::from_list_str(s)
+    }
+}
+
+/// The error returned when a string can't be parsed into a `Bitmap`.
+#[derive(Debug, PartialEq)]
+pub struct ParseBitmapError {
+    input: String,
+}
+
+impl ParseBitmapError {
+    pub(crate) fn new(input: &str) -> ParseBitmapError {
+        ParseBitmapError { input: input.to_string() }
+    }
+}
+
+impl fmt::Display for ParseBitmapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid bitmap string: {:?}", self.input)
+    }
+}
+
+impl Error for ParseBitmapError {}
+
+/// The error returned when a `Bitmap` does not fit into a fixed-size representation.
+#[derive(Debug, PartialEq)]
+pub enum BitmapConversionError {
+    /// The bitmap is infinite.
+    Infinite,
+    /// The bitmap contains an index which is too large for the target, like a CPU
+    /// beyond `CPU_SETSIZE` for a `cpu_set_t`.
+    IndexTooLarge(u32),
+}
+
+impl fmt::Display for BitmapConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BitmapConversionError::Infinite => write!(f, "bitmap is infinite"),
+            BitmapConversionError::IndexTooLarge(index) => {
+                write!(f, "bitmap index {} is too large", index)
+            }
+        }
+    }
+}
+
+impl Error for BitmapConversionError {}
+
+impl Clone for Bitmap {
+    fn clone(&self) -> Bitmap {
+        unsafe { Bitmap::from_raw(ffi::hwloc_bitmap_dup(self.bitmap)) }
+    }
+}
+
+impl PartialEq for Bitmap {
+    fn eq(&self, other: &Self) -> bool {
+        let result = unsafe { ffi::hwloc_bitmap_isequal(self.bitmap, other.as_ptr()) };
+        result == 1
+    }
+}
+
+impl Eq for Bitmap {}
+
+impl Hash for Bitmap {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // The ranges are finite even for infinite bitmaps and identify the set exactly.
+        for range in self.ranges() {
+            range.hash(state);
+        }
+    }
+}
+
+/// Orders bitmaps like `hwloc_bitmap_compare`, by their highest index first.
+///
+/// This is a total order which is consistent with equality, but it does not reflect
+/// inclusion: use `is_included_in` for that.
+impl PartialOrd for Bitmap {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Bitmap {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let result = unsafe { ffi::hwloc_bitmap_compare(self.bitmap, other.bitmap) };
+        result.cmp(&0)
+    }
+}
+
+impl IntoIterator for Bitmap {
+    type Item = u32;
+    type IntoIter = BitmapIntoIterator;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BitmapIntoIterator {
+            bitmap: self,
+            index: -1,
+        }
+    }
+}
+
+pub struct BitmapIntoIterator {
+    bitmap: Bitmap,
+    index: i32,
+}
+
+impl Iterator for BitmapIntoIterator {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let result = unsafe { ffi::hwloc_bitmap_next(self.bitmap.as_ptr(), self.index) };
+        self.index = result;
+        if result < 0 {
+            None
+        } else {
+            Some(result as u32)
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Bitmap {
+    type Item = u32;
+    type IntoIter = BitmapIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the set indexes of a borrowed `Bitmap`, see `Bitmap::iter`.
+pub struct BitmapIter<'a> {
+    bitmap: &'a Bitmap,
+    index: i32,
+    remaining: Option<usize>,
+}
+
+impl<'a> Iterator for BitmapIter<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+
+        let result = unsafe { ffi::hwloc_bitmap_next(self.bitmap.as_ptr(), self.index) };
+        if result < 0 {
+            self.remaining = Some(0);
+            return None;
+        }
+
+        self.index = result;
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining -= 1;
+        }
+        Some(result as u32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.remaining {
+            Some(remaining) => (remaining, Some(remaining)),
+            None => (usize::MAX, None),
+        }
+    }
+}
+
+/// Iterator over the set indexes of a finite `Bitmap`, see `Bitmap::try_iter`.
+pub struct BitmapFiniteIter<'a> {
+    inner: BitmapIter<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for BitmapFiniteIter<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let result = self.inner.next()?;
+        self.remaining -= 1;
+        Some(result)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for BitmapFiniteIter<'a> {}
+
+/// Iterator over the set indexes of a `Bitmap` in decreasing order, see `Bitmap::iter_rev`.
+pub struct BitmapRevIter {
+    remaining_bits: Bitmap,
+    remaining: usize,
+}
+
+impl Iterator for BitmapRevIter {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        // hwloc has no way to look up the previous index, so unset the last one instead.
+        let last = self.remaining_bits.last();
+        if last < 0 {
+            return None;
+        }
+
+        self.remaining_bits.unset(last as u32);
+        self.remaining -= 1;
+        Some(last as u32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for BitmapRevIter {}
+
+/// Iterator over the ranges of consecutive set indexes of a `Bitmap`, see `Bitmap::ranges`.
+pub struct BitmapRanges<'a> {
+    bitmap: &'a Bitmap,
+    next: i32,
+    infinite_from: Option<i32>,
+}
+
+impl<'a> Iterator for BitmapRanges<'a> {
+    type Item = (u32, i32);
+
+    fn next(&mut self) -> Option<(u32, i32)> {
+        if self.next < 0 {
+            return None;
+        }
+
+        let begin = self.next;
+        let mut end = begin;
+        loop {
+            if matches!(self.infinite_from, Some(from) if end >= from) {
+                self.next = -1;
+                return Some((begin as u32, -1));
+            }
+
+            let next = unsafe { ffi::hwloc_bitmap_next(self.bitmap.as_ptr(), end) };
+            if next != end + 1 {
+                self.next = next;
+                return Some((begin as u32, end));
+            }
+            end = next;
+        }
+    }
+}
+
+/// Iterator over the unset indexes of a `Bitmap` below a bound, see `Bitmap::iter_unset`.
+pub struct BitmapUnsetIter {
+    inner: BitmapIntoIterator,
+    remaining: usize,
+}
+
+impl Iterator for BitmapUnsetIter {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for BitmapUnsetIter {}
+
+impl FromIterator<u32> for Bitmap {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Bitmap {
+        let mut bitmap = Bitmap::new();
+        for i in iter {
+            bitmap.set(i);
+        }
+        bitmap
+    }
+}
+
+impl Default for Bitmap {
+    fn default() -> Self {
         Self::new()
     }
 }
 
+/// Implements the API of a typed set which wraps a `Bitmap`, like `CpuSet`.
+///
+/// Everything which only reads or modifies the set in place is reachable through `Deref`
+/// and `DerefMut`, so this only adds what has to return the typed set itself, and typed
+/// versions of the methods relating two sets, which shadow the ones taking any `Bitmap`.
+macro_rules! impl_bitmap_newtype {
+    ($set:ident) => {
+        impl $set {
+            #[doc = concat!("Creates an empty `", stringify!($set), "`.")]
+            pub fn new() -> $set {
+                $set(Bitmap::new())
+            }
+
+            #[doc = concat!("Creates a full `", stringify!($set), "`.")]
+            pub fn full() -> $set {
+                $set(Bitmap::full())
+            }
+
+            #[doc = concat!("Creates a new `", stringify!($set), "` and sets one index right away.")]
+            pub fn from(id: u32) -> $set {
+                $set(Bitmap::from(id))
+            }
+
+            #[doc = concat!("Creates a new `", stringify!($set), "` with the given range, see `Bitmap::from_range`.")]
+            pub fn from_range(begin: u32, end: i32) -> $set {
+                $set(Bitmap::from_range(begin, end))
+            }
+
+            #[doc = concat!("Reinterprets `bitmap` as a `", stringify!($set), "`.")]
+            pub fn from_bitmap(bitmap: Bitmap) -> $set {
+                $set(bitmap)
+            }
+
+            /// Returns the untyped `Bitmap` of this set.
+            pub fn into_bitmap(self) -> Bitmap {
+                self.0
+            }
+
+            #[doc = concat!("Wraps the given hwloc bitmap pointer into a `", stringify!($set), "`.")]
+            ///
+            /// # Safety
+            ///
+            /// See `Bitmap::from_raw`.
+            pub unsafe fn from_raw(bitmap: *mut IntHwlocBitmap) -> $set {
+                $set(Bitmap::from_raw(bitmap))
+            }
+
+            /// Test whether this set and `other` have at least one index in common.
+            pub fn intersects(&self, other: &$set) -> bool {
+                self.0.intersects(&other.0)
+            }
+
+            /// Test whether all indexes of this set are also set in `other`.
+            pub fn is_included_in(&self, other: &$set) -> bool {
+                self.0.is_included_in(&other.0)
+            }
+
+            /// Returns a new set with the indexes of this one which are not set in `other`.
+            pub fn andnot(&self, other: &$set) -> $set {
+                $set(self.0.andnot(&other.0))
+            }
+
+            /// Returns a new set with the indexes set in both this one and `other`.
+            pub fn and(&self, other: &$set) -> $set {
+                $set(self.0.and(&other.0))
+            }
+
+            /// Returns a new set with the indexes set in this one, `other` or both.
+            pub fn or(&self, other: &$set) -> $set {
+                $set(self.0.or(&other.0))
+            }
+
+            /// Returns a new set with the indexes set in either this one or `other`, but not
+            /// both.
+            pub fn xor(&self, other: &$set) -> $set {
+                $set(self.0.xor(&other.0))
+            }
+
+            /// Compares this set with `other` using their lowest index, see
+            /// `Bitmap::compare_first`.
+            pub fn compare_first(&self, other: &$set) -> Ordering {
+                self.0.compare_first(&other.0)
+            }
+        }
+
+        impl Deref for $set {
+            type Target = Bitmap;
+
+            fn deref(&self) -> &Bitmap {
+                &self.0
+            }
+        }
+
+        impl DerefMut for $set {
+            fn deref_mut(&mut self) -> &mut Bitmap {
+                &mut self.0
+            }
+        }
+
+        impl AsRef<Bitmap> for $set {
+            fn as_ref(&self) -> &Bitmap {
+                &self.0
+            }
+        }
+
+        impl From<Bitmap> for $set {
+            fn from(bitmap: Bitmap) -> $set {
+                $set(bitmap)
+            }
+        }
+
+        impl From<$set> for Bitmap {
+            fn from(set: $set) -> Bitmap {
+                set.0
+            }
+        }
+
+        impl fmt::Display for $set {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        impl fmt::Debug for $set {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&self.0, f)
+            }
+        }
+
+        impl FromStr for $set {
+            type Err = ParseBitmapError;
+
+            fn from_str(s: &str) -> Result<$set, ParseBitmapError> {
+                Bitmap::from_list_str(s).map($set)
+            }
+        }
+
+        impl Not for $set {
+            type Output = $set;
+
+            fn not(self) -> $set {
+                $set(!self.0)
+            }
+        }
+
+        impl IntoIterator for $set {
+            type Item = u32;
+            type IntoIter = BitmapIntoIterator;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.into_iter()
+            }
+        }
+
+        impl<'a> IntoIterator for &'a $set {
+            type Item = u32;
+            type IntoIter = BitmapIter<'a>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.iter()
+            }
+        }
+
+        impl FromIterator<u32> for $set {
+            fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> $set {
+                $set(Bitmap::from_iter(iter))
+            }
+        }
+
+        impl_bitmap_newtype_op!($set, BitAnd, bitand, BitAndAssign, bitand_assign);
+        impl_bitmap_newtype_op!($set, BitOr, bitor, BitOrAssign, bitor_assign);
+        impl_bitmap_newtype_op!($set, BitXor, bitxor, BitXorAssign, bitxor_assign);
+        impl_bitmap_newtype_op!($set, Sub, sub, SubAssign, sub_assign);
+    }
+}
+
+/// Implements a binary set operator of a typed set on top of the one of `Bitmap`.
+macro_rules! impl_bitmap_newtype_op {
+    ($set:ident, $op:ident, $method:ident, $assign_op:ident, $assign_method:ident) => {
+        impl<'a, 'b> $op<&'b $set> for &'a $set {
+            type Output = $set;
+
+            fn $method(self, rhs: &'b $set) -> $set {
+                $set((&self.0).$method(&rhs.0))
+            }
+        }
+
+        impl $op<$set> for $set {
+            type Output = $set;
+
+            fn $method(self, rhs: $set) -> $set {
+                $set(self.0.$method(rhs.0))
+            }
+        }
+
+        impl<'b> $assign_op<&'b $set> for $set {
+            fn $assign_method(&mut self, rhs: &'b $set) {
+                self.0.$assign_method(&rhs.0)
+            }
+        }
+
+        impl $assign_op<$set> for $set {
+            fn $assign_method(&mut self, rhs: $set) {
+                self.0.$assign_method(rhs.0)
+            }
+        }
+    }
+}
+
+impl_bitmap_newtype!(CpuSet);
+impl_bitmap_newtype!(NodeSet);
+
 #[cfg(test)]
 mod tests {
 
@@ -614,6 +1722,200 @@ mod tests {
         assert_eq!(src, dst);
     }
 
+    #[test]
+    fn should_check_intersection_and_inclusion() {
+        let bitmap = Bitmap::from_range(2, 5);
+
+        assert!(bitmap.intersects(&Bitmap::from(5)));
+        assert!(!bitmap.intersects(&Bitmap::from(6)));
+        assert!(!bitmap.intersects(&Bitmap::new()));
+
+        assert!(bitmap.is_included_in(&Bitmap::full()));
+        assert!(Bitmap::new().is_included_in(&bitmap));
+        assert!(!bitmap.is_included_in(&Bitmap::from_range(3, 5)));
+    }
+
+    #[test]
+    fn should_compute_andnot() {
+        let bitmap = Bitmap::from_range(0, 7);
+        assert_eq!("0-2,6-7", format!("{}", bitmap.andnot(&Bitmap::from_range(3, 5))));
+        assert_eq!("", format!("{}", bitmap.andnot(&Bitmap::full())));
+    }
+
+    #[test]
+    fn should_compute_and_or_xor() {
+        let left = Bitmap::from_range(0, 5);
+        let right = Bitmap::from_range(4, 9);
+
+        assert_eq!("4-5", format!("{}", left.and(&right)));
+        assert_eq!("0-9", format!("{}", left.or(&right)));
+        assert_eq!("0-3,6-9", format!("{}", left.xor(&right)));
+        assert_eq!("6-", format!("{}", Bitmap::full().and(&!left.clone())));
+    }
+
+    #[test]
+    fn should_compare_first() {
+        let bitmap = Bitmap::from_range(3, 4);
+
+        assert_eq!(Ordering::Greater, bitmap.compare_first(&Bitmap::from(1)));
+        assert_eq!(Ordering::Equal, bitmap.compare_first(&Bitmap::from(3)));
+        assert_eq!(Ordering::Less, bitmap.compare_first(&Bitmap::from(5)));
+        assert_eq!(Ordering::Greater, Bitmap::new().compare_first(&bitmap));
+    }
+
+    #[test]
+    fn should_keep_typed_sets_through_operators() {
+        let left = CpuSet::from_range(0, 5);
+        let right = CpuSet::from_range(4, 9);
+
+        let both: CpuSet = &left & &right;
+        assert_eq!("4-5", both.to_string());
+        assert_eq!(CpuSet::from_range(0, 3), left.clone() - right);
+        assert_eq!("6-", (!left).to_string());
+
+        let nodeset: NodeSet = "0,2".parse().unwrap();
+        assert_eq!(vec![0, 2], nodeset.iter().collect::<Vec<_>>());
+        assert_eq!(Bitmap::from_range(0, 1), NodeSet::from_range(0, 1).into_bitmap());
+    }
+
+    #[test]
+    fn should_relate_typed_sets() {
+        let left = NodeSet::from_range(0, 5);
+        let right = NodeSet::from_range(4, 9);
+
+        assert!(left.intersects(&right));
+        assert!(!left.is_included_in(&right));
+        assert!(NodeSet::from(4).is_included_in(&right));
+        let both: NodeSet = left.and(&right);
+        assert_eq!("4-5", both.to_string());
+        assert_eq!("0-3", left.andnot(&right).to_string());
+        assert_eq!("0-9", left.or(&right).to_string());
+        assert_eq!("0-3,6-9", left.xor(&right).to_string());
+        assert_eq!(Ordering::Less, left.compare_first(&right));
+    }
+
+    #[test]
+    fn should_convert_to_and_from_ulongs() {
+        let ulong_bits = mem::size_of::<c_ulong>() as u32 * 8;
+        let mut bitmap = Bitmap::from_range(0, 3);
+        bitmap.set(ulong_bits + 1);
+
+        let ulongs = bitmap.to_ulongs().unwrap();
+        assert_eq!(vec![0xf, 0x2], ulongs);
+        assert_eq!(bitmap, Bitmap::from_ulongs(&ulongs));
+        assert!(Bitmap::from_ulongs(&[]).is_empty());
+
+        let mut infinite = Bitmap::new();
+        infinite.set_range(3, -1);
+        assert_eq!(Err(BitmapConversionError::Infinite), infinite.to_ulongs());
+    }
+
+    #[test]
+    fn should_order_by_highest_index() {
+        let mut sets = [Bitmap::full(), Bitmap::from_range(0, 7), Bitmap::new(),
+                        Bitmap::from(8), Bitmap::from_range(0, 3)];
+        sets.sort();
+
+        let sorted: Vec<String> = sets.iter().map(|set| set.to_string()).collect();
+        assert_eq!(vec!["", "0-3", "0-7", "8", "0-"], sorted);
+        assert_eq!(Ordering::Equal, Bitmap::from(3).cmp(&Bitmap::from(3)));
+    }
+
+    #[test]
+    fn should_use_bitmap_as_map_key() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let mut infinite = Bitmap::from_range(0, 3);
+        infinite.set_range(8, -1);
+
+        let mut hashed = HashMap::new();
+        hashed.insert(Bitmap::from_range(0, 3), "low");
+        hashed.insert(infinite.clone(), "infinite");
+        assert_eq!(Some(&"low"), hashed.get(&"0-3".parse::<Bitmap>().unwrap()));
+        assert_eq!(Some(&"infinite"), hashed.get(&"0-3,8-".parse::<Bitmap>().unwrap()));
+        assert_eq!(None, hashed.get(&Bitmap::from_range(0, 4)));
+
+        let mut ordered = BTreeMap::new();
+        ordered.insert(infinite, 2);
+        ordered.insert(Bitmap::from(1), 1);
+        assert_eq!(vec![1, 2], ordered.values().cloned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_support_set_operators() {
+        let left = Bitmap::from_range(0, 5);
+        let right = Bitmap::from_range(4, 9);
+
+        assert_eq!(Bitmap::from_range(4, 5), &left & &right);
+        assert_eq!(Bitmap::from_range(0, 9), &left | &right);
+        assert_eq!(left.xor(&right), &left ^ &right);
+        assert_eq!(Bitmap::from_range(0, 3), &left - &right);
+
+        assert_eq!(Bitmap::from_range(4, 5), left.clone() & right.clone());
+        assert_eq!(Bitmap::from_range(0, 9), left.clone() | &right);
+        assert_eq!(Bitmap::from_range(6, 9), &right - left.clone());
+
+        // The operands are left untouched.
+        assert_eq!(Bitmap::from_range(0, 5), left);
+        assert_eq!(Bitmap::from_range(4, 9), right);
+    }
+
+    #[test]
+    fn should_support_assigning_set_operators() {
+        let mut bitmap = Bitmap::from_range(0, 5);
+
+        bitmap &= Bitmap::from_range(2, 9);
+        assert_eq!("2-5", format!("{}", bitmap));
+
+        bitmap |= &Bitmap::from(8);
+        assert_eq!("2-5,8", format!("{}", bitmap));
+
+        bitmap ^= Bitmap::from_range(5, 8);
+        assert_eq!("2-4,6-7", format!("{}", bitmap));
+
+        bitmap -= &Bitmap::from_range(3, 6);
+        assert_eq!("2,7", format!("{}", bitmap));
+    }
+
+    #[test]
+    fn should_format_in_all_syntaxes() {
+        let mut bitmap = Bitmap::from_range(0, 3);
+        bitmap.set(36);
+
+        assert_eq!("0-3,36", bitmap.to_list_string());
+        assert_eq!("0x00000010,0x0000000f", bitmap.to_hex_string());
+        assert_eq!("0x100000000f", bitmap.to_taskset_string());
+    }
+
+    #[test]
+    fn should_parse_all_syntaxes() {
+        let expected = "0-3,36".parse::<Bitmap>().unwrap();
+
+        assert_eq!(expected, Bitmap::from_list_str("0-3,36").unwrap());
+        assert_eq!(expected, Bitmap::from_hex_str("0x00000010,0x0000000f").unwrap());
+        assert_eq!(expected, Bitmap::from_taskset_str("0x100000000f").unwrap());
+    }
+
+    #[test]
+    fn should_roundtrip_strings() {
+        let mut bitmap = Bitmap::from_range(2, 40);
+        bitmap.set_range(70, -1);
+        bitmap.unset(9);
+
+        assert_eq!(bitmap, Bitmap::from_list_str(&bitmap.to_list_string()).unwrap());
+        assert_eq!(bitmap, Bitmap::from_hex_str(&bitmap.to_hex_string()).unwrap());
+        assert_eq!(bitmap, Bitmap::from_taskset_str(&bitmap.to_taskset_string()).unwrap());
+        assert_eq!(bitmap, format!("{}", bitmap).parse::<Bitmap>().unwrap());
+    }
+
+    #[test]
+    fn should_fail_to_parse_invalid_strings() {
+        assert!("0-3,x".parse::<Bitmap>().is_err());
+        assert!("0-3\0,4".parse::<Bitmap>().is_err());
+        assert!(Bitmap::from_hex_str("not hex").is_err());
+        assert!(Bitmap::from_taskset_str("0xzz").is_err());
+    }
+
     #[test]
     fn should_support_into_iter() {
         let mut bitmap = Bitmap::from_range(4, 8);
@@ -624,6 +1926,93 @@ mod tests {
         assert_eq!(vec![2, 4, 5, 6, 7, 8], collected);
     }
 
+    #[test]
+    fn should_iterate_by_reference() {
+        let mut bitmap = Bitmap::from_range(4, 6);
+        bitmap.set(1);
+
+        let mut iter = bitmap.iter();
+        assert_eq!((4, Some(4)), iter.size_hint());
+        assert_eq!(vec![1, 4, 5, 6], iter.by_ref().collect::<Vec<u32>>());
+        assert_eq!(None, iter.next());
+
+        let mut collected = Vec::new();
+        for idx in &bitmap {
+            collected.push(idx);
+        }
+        assert_eq!(vec![1, 4, 5, 6], collected);
+        assert_eq!(4, bitmap.weight());
+    }
+
+    #[test]
+    fn should_iterate_infinite_bitmap_by_reference() {
+        let bitmap = Bitmap::from_range(3, -1);
+
+        assert_eq!((usize::MAX, None), bitmap.iter().size_hint());
+        assert_eq!(vec![3, 4, 5, 6], bitmap.iter().take(4).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn should_iterate_finite_bitmap_with_exact_size() {
+        let mut bitmap = Bitmap::from_range(0, 2);
+        bitmap.set(70);
+
+        let mut iter = bitmap.try_iter().unwrap();
+        assert_eq!(4, iter.len());
+        assert_eq!(Some(0), iter.next());
+        assert_eq!(3, iter.len());
+        assert_eq!(vec![1, 2, 70], iter.collect::<Vec<u32>>());
+        assert_eq!(0, Bitmap::new().try_iter().unwrap().len());
+
+        bitmap.set_range(100, -1);
+        assert!(bitmap.try_iter().is_none());
+    }
+
+    #[test]
+    fn should_iterate_in_reverse() {
+        let mut bitmap = Bitmap::from_range(0, 2);
+        bitmap.set(70);
+
+        let iter = bitmap.iter_rev();
+        assert_eq!(4, iter.len());
+        assert_eq!(vec![70, 2, 1, 0], iter.collect::<Vec<u32>>());
+        assert_eq!(0, Bitmap::new().iter_rev().count());
+
+        let sparse: Bitmap = vec![0, 1_000_000].into_iter().collect();
+        assert_eq!(vec![1_000_000, 0], sparse.iter_rev().collect::<Vec<u32>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_panic_iterating_infinite_bitmap_in_reverse() {
+        Bitmap::full().iter_rev();
+    }
+
+    #[test]
+    fn should_iterate_ranges() {
+        let mut bitmap = Bitmap::from_range(0, 3);
+        bitmap.set(5);
+        bitmap.set_range(8, 11);
+        assert_eq!(vec![(0, 3), (5, 5), (8, 11)], bitmap.ranges().collect::<Vec<_>>());
+
+        bitmap.set_range(12, -1);
+        assert_eq!(vec![(0, 3), (5, 5), (8, -1)], bitmap.ranges().collect::<Vec<_>>());
+
+        assert_eq!(vec![(0, -1)], Bitmap::full().ranges().collect::<Vec<_>>());
+        assert_eq!(0, Bitmap::new().ranges().count());
+    }
+
+    #[test]
+    fn should_iterate_unset_indexes() {
+        let mut bitmap = Bitmap::from_range(1, 2);
+        bitmap.set_range(5, -1);
+
+        assert_eq!(vec![0, 3, 4], bitmap.iter_unset(64).collect::<Vec<u32>>());
+        assert_eq!(vec![0], bitmap.iter_unset(2).collect::<Vec<u32>>());
+        assert_eq!(0, bitmap.iter_unset(0).len());
+        assert_eq!(10, Bitmap::new().iter_unset(10).len());
+    }
+
     #[test]
     fn should_support_from_iter() {
         let bitmap = (1..10).collect::<Bitmap>();
@@ -0,0 +1,407 @@
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+use std::{fmt, mem, slice};
+
+use libc::{self, c_int, c_void};
+
+use bitmap::NodeSet;
+use ffi;
+use {Topology, MemBindError, MemBindFlags, MemBindPolicy, MEMBIND_BYNODESET};
+
+/// A memory area allocated by `hwloc_alloc_membind` and freed with `hwloc_free` on drop.
+struct RawNumaAlloc<'t> {
+    topology: &'t Topology,
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl<'t> RawNumaAlloc<'t> {
+    fn new(topology: &'t Topology,
+           len: usize,
+           set: &NodeSet,
+           policy: MemBindPolicy,
+           flags: MemBindFlags)
+           -> Result<RawNumaAlloc<'t>, MemBindError> {
+        if len == 0 {
+            // hwloc can't allocate empty areas, nothing would be bound anyway.
+            return Ok(RawNumaAlloc { topology, ptr: NonNull::dangling(), len });
+        }
+
+        let ptr = unsafe {
+            ffi::hwloc_alloc_membind(topology.topo,
+                                     len,
+                                     set.as_ptr(),
+                                     policy as c_int,
+                                     (flags | MEMBIND_BYNODESET).bits())
+        };
+        match NonNull::new(ptr as *mut u8) {
+            Some(ptr) => Ok(RawNumaAlloc { topology, ptr, len }),
+            None => Err(MemBindError::from_errno()),
+        }
+    }
+
+    /// Checks that the area is aligned for values of type `T`.
+    ///
+    /// hwloc allocates whole pages, so this only fails for over-aligned types.
+    fn check_align<T>(mut self) -> Result<RawNumaAlloc<'t>, MemBindError> {
+        if self.len == 0 {
+            // Empty areas are never freed, any well-aligned address will do.
+            self.ptr = unsafe { NonNull::new_unchecked(mem::align_of::<T>() as *mut u8) };
+        }
+        // Alignments are powers of two, so the low bits of an aligned address are clear.
+        if self.ptr.as_ptr() as usize & (mem::align_of::<T>() - 1) == 0 {
+            Ok(self)
+        } else {
+            Err(MemBindError::Generic(libc::EINVAL,
+                                      format!("alignment of {} bytes is not supported",
+                                              mem::align_of::<T>())))
+        }
+    }
+}
+
+impl<'t> Drop for RawNumaAlloc<'t> {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                ffi::hwloc_free(self.topology.topo, self.ptr.as_ptr() as *mut c_void, self.len);
+            }
+        }
+    }
+}
+
+/// A zero-initialized byte buffer whose memory is bound to NUMA nodes.
+///
+/// Created through `Topology::alloc_membind`. The buffer dereferences to `[u8]` and
+/// returns its memory to hwloc when dropped.
+///
+/// # Examples
+///
+/// ```
+/// use hwloc::{Topology, NodeSet, MemBindPolicy, MemBindFlags};
+///
+/// let topology = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+/// let mut buffer = topology.alloc_membind(4096, &NodeSet::from(1), MemBindPolicy::Bind,
+///                                         MemBindFlags::empty())
+///     .unwrap();
+///
+/// buffer[0] = 42;
+/// assert_eq!(4096, buffer.len());
+/// assert_eq!(42, buffer[0]);
+/// ```
+pub struct NumaBuffer<'t> {
+    raw: RawNumaAlloc<'t>,
+}
+
+impl<'t> NumaBuffer<'t> {
+    pub(crate) fn new(topology: &'t Topology,
+                      len: usize,
+                      set: &NodeSet,
+                      policy: MemBindPolicy,
+                      flags: MemBindFlags)
+                      -> Result<NumaBuffer<'t>, MemBindError> {
+        let raw = RawNumaAlloc::new(topology, len, set, policy, flags)?;
+        // Zeroing makes the contents initialized, and touching the pages allocates them
+        // according to the policy.
+        unsafe { ptr::write_bytes(raw.ptr.as_ptr(), 0, len) };
+        Ok(NumaBuffer { raw })
+    }
+
+    /// Returns the NUMA nodes where the pages of this buffer currently are.
+    ///
+    /// See `Topology::get_area_memlocation`.
+    pub fn memlocation(&self) -> Result<NodeSet, MemBindError> {
+        self.raw.topology.get_area_memlocation(self, MemBindFlags::empty())
+    }
+}
+
+impl<'t> Deref for NumaBuffer<'t> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.raw.ptr.as_ptr(), self.raw.len) }
+    }
+}
+
+impl<'t> DerefMut for NumaBuffer<'t> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.raw.ptr.as_ptr(), self.raw.len) }
+    }
+}
+
+impl<'t> fmt::Debug for NumaBuffer<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NumaBuffer").field("len", &self.raw.len).finish()
+    }
+}
+
+/// A single value of type `T` stored in memory bound to NUMA nodes.
+///
+/// # Examples
+///
+/// ```
+/// use hwloc::{Topology, NodeSet, NumaBox, MemBindPolicy, MemBindFlags};
+///
+/// let topology = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+/// let mut value = NumaBox::new(&topology, [0u64; 64], &NodeSet::from(0), MemBindPolicy::Bind,
+///                              MemBindFlags::empty())
+///     .unwrap();
+///
+/// value[3] = 7;
+/// assert_eq!(7, value.iter().sum::<u64>());
+/// ```
+pub struct NumaBox<'t, T> {
+    raw: RawNumaAlloc<'t>,
+    marker: PhantomData<T>,
+}
+
+impl<'t, T> NumaBox<'t, T> {
+    /// Moves `value` into memory allocated on the NUMA nodes in `set`.
+    pub fn new(topology: &'t Topology,
+               value: T,
+               set: &NodeSet,
+               policy: MemBindPolicy,
+               flags: MemBindFlags)
+               -> Result<NumaBox<'t, T>, MemBindError> {
+        let raw = RawNumaAlloc::new(topology, mem::size_of::<T>(), set, policy, flags)?
+            .check_align::<T>()?;
+        unsafe { ptr::write(raw.ptr.as_ptr() as *mut T, value) };
+        Ok(NumaBox { raw, marker: PhantomData })
+    }
+
+    /// Returns the NUMA nodes where the pages of this value currently are.
+    pub fn memlocation(&self) -> Result<NodeSet, MemBindError> {
+        let bytes = unsafe { slice::from_raw_parts(self.raw.ptr.as_ptr(), self.raw.len) };
+        self.raw.topology.get_area_memlocation(bytes, MemBindFlags::empty())
+    }
+}
+
+impl<'t, T> Deref for NumaBox<'t, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*(self.raw.ptr.as_ptr() as *const T) }
+    }
+}
+
+impl<'t, T> DerefMut for NumaBox<'t, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *(self.raw.ptr.as_ptr() as *mut T) }
+    }
+}
+
+impl<'t, T> Drop for NumaBox<'t, T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.raw.ptr.as_ptr() as *mut T) }
+    }
+}
+
+impl<'t, T: fmt::Debug> fmt::Debug for NumaBox<'t, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// A growable vector of `T` whose storage is bound to NUMA nodes.
+///
+/// Growing the vector allocates new storage with the same nodes, policy and flags and
+/// moves the elements over, so `push` can fail just like the initial allocation.
+///
+/// # Examples
+///
+/// ```
+/// use hwloc::{Topology, NodeSet, NumaVec, MemBindPolicy, MemBindFlags};
+///
+/// let topology = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+/// let mut vec = NumaVec::with_capacity(&topology, 2, &NodeSet::from_range(0, 1),
+///                                      MemBindPolicy::Interleave, MemBindFlags::empty())
+///     .unwrap();
+///
+/// for i in 0..10u32 {
+///     vec.push(i).unwrap();
+/// }
+/// assert_eq!(45, vec.iter().sum::<u32>());
+/// ```
+pub struct NumaVec<'t, T> {
+    raw: RawNumaAlloc<'t>,
+    len: usize,
+    set: NodeSet,
+    policy: MemBindPolicy,
+    flags: MemBindFlags,
+    marker: PhantomData<T>,
+}
+
+impl<'t, T> NumaVec<'t, T> {
+    /// Creates an empty vector with room for `capacity` elements on the NUMA nodes in `set`.
+    pub fn with_capacity(topology: &'t Topology,
+                         capacity: usize,
+                         set: &NodeSet,
+                         policy: MemBindPolicy,
+                         flags: MemBindFlags)
+                         -> Result<NumaVec<'t, T>, MemBindError> {
+        let raw = NumaVec::<T>::alloc(topology, capacity, set, policy, flags)?;
+        Ok(NumaVec {
+            raw,
+            len: 0,
+            set: set.clone(),
+            policy,
+            flags,
+            marker: PhantomData,
+        })
+    }
+
+    fn alloc(topology: &'t Topology,
+             capacity: usize,
+             set: &NodeSet,
+             policy: MemBindPolicy,
+             flags: MemBindFlags)
+             -> Result<RawNumaAlloc<'t>, MemBindError> {
+        let len = match capacity.checked_mul(mem::size_of::<T>()) {
+            Some(len) => len,
+            None => return Err(MemBindError::Generic(libc::ENOMEM, "capacity overflow".into())),
+        };
+        RawNumaAlloc::new(topology, len, set, policy, flags)?.check_align::<T>()
+    }
+
+    /// The number of elements the vector can hold without allocating.
+    pub fn capacity(&self) -> usize {
+        match mem::size_of::<T>() {
+            0 => usize::MAX,
+            size => self.raw.len / size,
+        }
+    }
+
+    /// Appends `value`, growing the bound storage if needed.
+    pub fn push(&mut self, value: T) -> Result<(), MemBindError> {
+        if self.len == self.capacity() {
+            let capacity = self.capacity().saturating_mul(2).max(4);
+            let raw = NumaVec::<T>::alloc(self.raw.topology,
+                                          capacity,
+                                          &self.set,
+                                          self.policy,
+                                          self.flags)?;
+            unsafe {
+                ptr::copy_nonoverlapping(self.data(), raw.ptr.as_ptr() as *mut T, self.len)
+            };
+            self.raw = raw;
+        }
+
+        unsafe { ptr::write(self.data().add(self.len), value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the last element and returns it, or `None` if the vector is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { ptr::read(self.data().add(self.len)) })
+    }
+
+    /// The start of the storage. Accesses beyond `len` must go through this rather than
+    /// the slice returned by `Deref`, which only covers the first `len` elements.
+    fn data(&self) -> *mut T {
+        self.raw.ptr.as_ptr() as *mut T
+    }
+
+    /// Drops all elements, keeping the storage.
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+    }
+
+    /// Returns the NUMA nodes where the pages of the storage currently are.
+    pub fn memlocation(&self) -> Result<NodeSet, MemBindError> {
+        let bytes = unsafe { slice::from_raw_parts(self.raw.ptr.as_ptr(), self.raw.len) };
+        self.raw.topology.get_area_memlocation(bytes, MemBindFlags::empty())
+    }
+}
+
+impl<'t, T> Deref for NumaVec<'t, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.raw.ptr.as_ptr() as *const T, self.len) }
+    }
+}
+
+impl<'t, T> DerefMut for NumaVec<'t, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.raw.ptr.as_ptr() as *mut T, self.len) }
+    }
+}
+
+impl<'t, T> Drop for NumaVec<'t, T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<'t, T: fmt::Debug> fmt::Debug for NumaVec<'t, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn should_allocate_zeroed_buffer() {
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+        let mut buffer = topo.alloc_membind(10000, &NodeSet::from(1), MemBindPolicy::Bind,
+                                            MemBindFlags::empty())
+            .unwrap();
+
+        assert_eq!(10000, buffer.len());
+        assert!(buffer.iter().all(|&b| b == 0));
+        buffer[9999] = 1;
+        assert_eq!(1, buffer.iter().map(|&b| b as u32).sum::<u32>());
+
+        let empty = topo.alloc_membind(0, &NodeSet::from(1), MemBindPolicy::Bind,
+                                       MemBindFlags::empty())
+            .unwrap();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn should_drop_boxed_value() {
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+        let value = Rc::new(5);
+
+        let boxed = NumaBox::new(&topo, value.clone(), &NodeSet::from(0), MemBindPolicy::Bind,
+                                 MemBindFlags::empty())
+            .unwrap();
+        assert_eq!(5, **boxed);
+        assert_eq!(2, Rc::strong_count(&value));
+
+        drop(boxed);
+        assert_eq!(1, Rc::strong_count(&value));
+    }
+
+    #[test]
+    fn should_grow_and_drop_vec() {
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+        let value = Rc::new(5);
+
+        let mut vec = NumaVec::with_capacity(&topo, 1, &NodeSet::from(0), MemBindPolicy::Bind,
+                                             MemBindFlags::empty())
+            .unwrap();
+        for _ in 0..100 {
+            vec.push(value.clone()).unwrap();
+        }
+        assert_eq!(100, vec.len());
+        assert!(vec.capacity() >= 100);
+        assert_eq!(101, Rc::strong_count(&value));
+
+        assert!(vec.pop().is_some());
+        assert_eq!(100, Rc::strong_count(&value));
+
+        drop(vec);
+        assert_eq!(1, Rc::strong_count(&value));
+    }
+}
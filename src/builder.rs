@@ -0,0 +1,389 @@
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use errno::errno;
+use libc::c_int;
+use num::ToPrimitive;
+
+use ffi::{self, ObjectType, TopologyFlag};
+use {pid_t, Topology, TopologyError};
+
+/// Where the topology information is discovered from.
+enum Source {
+    ThisSystem,
+    XmlFile(PathBuf),
+    XmlBuffer(String),
+    Synthetic(String),
+    Pid(pid_t),
+    #[cfg(target_os = "linux")]
+    FsRoot(PathBuf),
+}
+
+/// Configures and loads a `Topology`.
+///
+/// The builder collects the discovery flags, the source the topology is read
+/// from and the object types to ignore, and only talks to hwloc once `build`
+/// is called. Every hwloc call made during the build is checked, so either a
+/// fully loaded `Topology` or a `TopologyError` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use hwloc::{TopologyBuilder, TopologyFlag};
+///
+/// let topology = TopologyBuilder::new()
+///     .flag(TopologyFlag::IoDevices)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(vec![TopologyFlag::IoDevices], topology.flags());
+/// ```
+pub struct TopologyBuilder {
+    flags: u64,
+    source: Source,
+    ignored_types: Vec<ObjectType>,
+    structure_ignored_types: Vec<ObjectType>,
+    ignore_all_keep_structure: bool,
+}
+
+impl TopologyBuilder {
+    /// Creates a builder which discovers the topology of the current system
+    /// without any flags set.
+    pub fn new() -> TopologyBuilder {
+        TopologyBuilder {
+            flags: 0,
+            source: Source::ThisSystem,
+            ignored_types: Vec::new(),
+            structure_ignored_types: Vec::new(),
+            ignore_all_keep_structure: false,
+        }
+    }
+
+    /// Adds a flag which customizes the topology discovery process.
+    pub fn flag(mut self, flag: TopologyFlag) -> TopologyBuilder {
+        self.flags |= flag.to_u64().unwrap();
+        self
+    }
+
+    /// Discovers the topology of the current system (the default).
+    pub fn this_system(mut self) -> TopologyBuilder {
+        self.source = Source::ThisSystem;
+        self
+    }
+
+    /// Reads the topology from the XML file at `path`.
+    pub fn xml_file<P: AsRef<Path>>(mut self, path: P) -> TopologyBuilder {
+        self.source = Source::XmlFile(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Reads the topology from an in-memory XML description.
+    pub fn xml_buffer(mut self, xml: &str) -> TopologyBuilder {
+        self.source = Source::XmlBuffer(xml.to_string());
+        self
+    }
+
+    /// Builds a synthetic topology from a description like `"pack:2 core:4 pu:2"`.
+    pub fn synthetic(mut self, description: &str) -> TopologyBuilder {
+        self.source = Source::Synthetic(description.to_string());
+        self
+    }
+
+    /// Discovers the topology as seen by the process identified by `pid`.
+    ///
+    /// On Linux this only affects the set of allowed CPUs and memory nodes,
+    /// which are read from the given process instead of the current one.
+    pub fn pid(mut self, pid: pid_t) -> TopologyBuilder {
+        self.source = Source::Pid(pid);
+        self
+    }
+
+    /// Discovers the topology from a filesystem tree rooted at `path` instead of `/`.
+    ///
+    /// This allows loading the topology of another machine from a snapshot of
+    /// its `/sys` and `/proc` directories. Unless `path` is `/`, the resulting
+    /// topology is not considered to be the current system, see
+    /// `Topology::is_this_system`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hwloc::TopologyBuilder;
+    ///
+    /// let topology = TopologyBuilder::new()
+    ///     .fsroot("/tmp/customer-snapshot")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert!(!topology.is_this_system());
+    /// ```
+    #[cfg(target_os = "linux")]
+    pub fn fsroot<P: AsRef<Path>>(mut self, path: P) -> TopologyBuilder {
+        self.source = Source::FsRoot(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Ignores all objects of the given type during discovery.
+    ///
+    /// The bottom-level type `ObjectType::PU` may not be ignored, and I/O object
+    /// types are controlled through the I/O topology flags instead. The
+    /// topology structure is reorganized so that all children of ignored
+    /// objects are attached to their parent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{TopologyBuilder, ObjectType};
+    ///
+    /// let topology = TopologyBuilder::new()
+    ///     .synthetic("pack:2 l2:2 core:2 pu:1")
+    ///     .ignore_type(ObjectType::Cache)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert!(topology.objects_with_type(&ObjectType::Cache).is_err());
+    /// ```
+    pub fn ignore_type(mut self, object_type: ObjectType) -> TopologyBuilder {
+        self.ignored_types.push(object_type);
+        self
+    }
+
+    /// Ignores objects of the given type if they do not bring any structure.
+    ///
+    /// An object is removed only if it has a single child or if its CPU set is
+    /// equal to the one of its single parent.
+    pub fn ignore_type_keep_structure(mut self, object_type: ObjectType) -> TopologyBuilder {
+        self.structure_ignored_types.push(object_type);
+        self
+    }
+
+    /// Ignores all objects that do not bring any structure.
+    ///
+    /// This works like `ignore_type_keep_structure`, but for every object type.
+    pub fn ignore_all_keep_structure(mut self) -> TopologyBuilder {
+        self.ignore_all_keep_structure = true;
+        self
+    }
+
+    /// Initializes, configures and loads the `Topology`.
+    ///
+    /// # Failures
+    ///
+    /// Returns a `TopologyError` describing the first step which failed. The
+    /// hwloc handle is always cleaned up on failure.
+    pub fn build(self) -> Result<Topology, TopologyError> {
+        let mut topo: *mut ffi::HwlocTopology = ptr::null_mut();
+
+        if unsafe { ffi::hwloc_topology_init(&mut topo) } < 0 {
+            let e = errno();
+            return Err(TopologyError::Init(e.0 as i32, format!("{}", e)));
+        }
+
+        // From here on the handle is owned by `topology`, so returning early
+        // destroys it through `Drop`.
+        let mut topology = Topology {
+            topo,
+            support: ptr::null(),
+        };
+
+        check_config(unsafe { ffi::hwloc_topology_set_flags(topology.topo, self.flags) })?;
+
+        match self.source {
+            Source::ThisSystem => {}
+            Source::XmlFile(ref path) => {
+                let path = path_to_cstring(path)?;
+                check_config(unsafe { ffi::hwloc_topology_set_xml(topology.topo, path.as_ptr()) })?;
+            }
+            Source::XmlBuffer(ref xml) => {
+                let xml = str_to_cstring(xml)?;
+                let len = xml.as_bytes_with_nul().len() as c_int;
+                check_config(unsafe {
+                    ffi::hwloc_topology_set_xmlbuffer(topology.topo, xml.as_ptr(), len)
+                })?;
+            }
+            Source::Synthetic(ref description) => {
+                let description = str_to_cstring(description)?;
+                check_config(unsafe {
+                    ffi::hwloc_topology_set_synthetic(topology.topo, description.as_ptr())
+                })?;
+            }
+            Source::Pid(pid) => {
+                check_config(unsafe { ffi::hwloc_topology_set_pid(topology.topo, pid) })?;
+            }
+            #[cfg(target_os = "linux")]
+            Source::FsRoot(ref path) => {
+                let path = path_to_cstring(path)?;
+                check_config(unsafe {
+                    ffi::hwloc_topology_set_fsroot(topology.topo, path.as_ptr())
+                })?;
+            }
+        }
+
+        for object_type in self.ignored_types {
+            check_config(unsafe { ffi::hwloc_topology_ignore_type(topology.topo, object_type) })?;
+        }
+
+        for object_type in self.structure_ignored_types {
+            check_config(unsafe {
+                ffi::hwloc_topology_ignore_type_keep_structure(topology.topo, object_type)
+            })?;
+        }
+
+        if self.ignore_all_keep_structure {
+            check_config(unsafe { ffi::hwloc_topology_ignore_all_keep_structure(topology.topo) })?;
+        }
+
+        if unsafe { ffi::hwloc_topology_load(topology.topo) } < 0 {
+            let e = errno();
+            return Err(TopologyError::Load(e.0 as i32, format!("{}", e)));
+        }
+
+        topology.support = unsafe { ffi::hwloc_topology_get_support(topology.topo) };
+        Ok(topology)
+    }
+}
+
+impl Default for TopologyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps the return code of a topology configuration call to a `TopologyError`.
+fn check_config(result: c_int) -> Result<(), TopologyError> {
+    match result {
+        r if r < 0 => {
+            let e = errno();
+            Err(TopologyError::Config(e.0 as i32, format!("{}", e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn str_to_cstring(s: &str) -> Result<CString, TopologyError> {
+    CString::new(s).map_err(|e| TopologyError::InvalidArgument(format!("{}", e)))
+}
+
+pub fn path_to_cstring(path: &Path) -> Result<CString, TopologyError> {
+    match path.to_str() {
+        Some(s) => str_to_cstring(s),
+        None => Err(TopologyError::InvalidArgument(format!("{} is not valid UTF-8", path.display()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use ObjectType;
+
+    #[test]
+    fn should_build_default_topology() {
+        let topo = TopologyBuilder::new().build().unwrap();
+        assert!(topo.depth() > 0);
+        assert_eq!(0, topo.flags().len());
+    }
+
+    #[test]
+    fn should_build_with_flags() {
+        let topo = TopologyBuilder::new()
+            .flag(TopologyFlag::WholeSystem)
+            .flag(TopologyFlag::IoBridges)
+            .build()
+            .unwrap();
+        assert_eq!(vec![TopologyFlag::WholeSystem, TopologyFlag::IoBridges],
+                   topo.flags());
+    }
+
+    #[test]
+    fn should_build_synthetic() {
+        let topo = TopologyBuilder::new().synthetic("pack:2 core:2 pu:2").build().unwrap();
+        assert_eq!(8, topo.objects_with_type(&ObjectType::PU).unwrap().len());
+    }
+
+    #[test]
+    fn should_use_last_configured_source() {
+        let topo = TopologyBuilder::new()
+            .synthetic("pack:2 core:2 pu:2")
+            .synthetic("pack:3 pu:1")
+            .build()
+            .unwrap();
+        assert_eq!(3, topo.objects_with_type(&ObjectType::PU).unwrap().len());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn should_build_from_root_fsroot() {
+        let topo = TopologyBuilder::new().fsroot("/").build().unwrap();
+        assert!(topo.is_this_system());
+        assert_eq!(Topology::new().depth(), topo.depth());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn should_fail_on_missing_fsroot() {
+        assert!(TopologyBuilder::new().fsroot("/this/path/does/not/exist").build().is_err());
+    }
+
+    #[test]
+    fn should_ignore_type() {
+        let topo = TopologyBuilder::new()
+            .synthetic("pack:2 l2:2 core:2 pu:1")
+            .ignore_type(ObjectType::Cache)
+            .build()
+            .unwrap();
+        assert_eq!(4, topo.depth());
+        assert!(topo.objects_with_type(&ObjectType::Cache).is_err());
+        assert_eq!(8, topo.objects_with_type(&ObjectType::Core).unwrap().len());
+    }
+
+    #[test]
+    fn should_ignore_type_keeping_structure() {
+        let topo = TopologyBuilder::new()
+            .synthetic("pack:1 core:2 pu:1")
+            .ignore_type_keep_structure(ObjectType::Package)
+            .build()
+            .unwrap();
+        assert!(topo.objects_with_type(&ObjectType::Package).is_err());
+
+        let topo = TopologyBuilder::new()
+            .synthetic("pack:2 core:2 pu:1")
+            .ignore_type_keep_structure(ObjectType::Package)
+            .build()
+            .unwrap();
+        assert_eq!(2, topo.objects_with_type(&ObjectType::Package).unwrap().len());
+    }
+
+    #[test]
+    fn should_ignore_all_keeping_structure() {
+        let topo = TopologyBuilder::new()
+            .synthetic("pack:2 l2:1 core:2 pu:1")
+            .ignore_all_keep_structure()
+            .build()
+            .unwrap();
+        assert!(topo.objects_with_type(&ObjectType::Cache).is_err());
+        assert_eq!(2, topo.objects_with_type(&ObjectType::Package).unwrap().len());
+    }
+
+    #[test]
+    fn should_refuse_to_ignore_pus() {
+        assert!(TopologyBuilder::new().ignore_type(ObjectType::PU).build().is_err());
+    }
+
+    #[test]
+    fn should_report_invalid_arguments() {
+        match TopologyBuilder::new().synthetic("pack:2\0").build() {
+            Err(TopologyError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result: {:?}", other.map(|t| t.depth())),
+        }
+    }
+
+    #[test]
+    fn should_report_config_errors() {
+        match TopologyBuilder::new().synthetic("not a description").build() {
+            Err(TopologyError::Config(_, _)) => {}
+            other => panic!("unexpected result: {:?}", other.map(|t| t.depth())),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use libc::{c_int, c_uint, c_ulonglong, c_char};
+use libc::{c_int, c_uint, c_ulong, c_ulonglong, c_char, c_void, size_t};
 use {pid_t, pthread_t};
 use num::{ToPrimitive, FromPrimitive};
 use topology_object::TopologyObject;
@@ -6,13 +6,13 @@ use bitmap::IntHwlocBitmap;
 use std::cmp::{PartialOrd, Ordering};
 use support::TopologySupport;
 
-// Added by long 20210707
-use MemBindPolicy;
-
 pub enum HwlocTopology {}
 
 /// Represents the type of a topology object.
 ///
+/// Equality and hashing are structural, so object types can be used as keys in a
+/// `HashMap` or `HashSet`.
+///
 /// Note that (partial) ordering for object types is implemented as a call
 /// into the `hwloc` library which defines ordering as follows:
 ///
@@ -22,9 +22,11 @@ pub enum HwlocTopology {}
 ///
 /// It can also help to think of it as comparing the relative depths of each type, so
 /// a `ObjectType::System` will be smaller than a `ObjectType::PU` since the system
-/// contains processing units.
+/// contains processing units. Types which cannot be compared, like I/O object types,
+/// are unordered. See `compare_inclusion`.
 #[repr(u32)]
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ObjectType {
     /// The whole system that is accessible to hwloc. That may comprise several
     /// machines in SSI systems like Kerrighed.
@@ -87,24 +89,37 @@ pub enum ObjectType {
     TypeMax,
 }
 
-impl PartialOrd for ObjectType {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+/// Returned by `hwloc_compare_types` if the types cannot be compared.
+const TYPE_UNORDERED: c_int = c_int::MAX;
+
+impl ObjectType {
+    /// Compares two object types by inclusion, as defined by hwloc.
+    ///
+    /// Returns `None` if the types cannot be compared, for example because one of them
+    /// is an I/O object type.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// use hwloc::ObjectType;
+    /// use std::cmp::Ordering;
+    ///
+    /// assert_eq!(Some(Ordering::Less), ObjectType::Machine.compare_inclusion(&ObjectType::PU));
+    /// assert_eq!(Some(Ordering::Equal), ObjectType::Core.compare_inclusion(&ObjectType::Core));
+    /// assert_eq!(None, ObjectType::Core.compare_inclusion(&ObjectType::PCIDevice));
+    /// ```
+    pub fn compare_inclusion(&self, other: &ObjectType) -> Option<Ordering> {
         let compared = unsafe { hwloc_compare_types(self.clone(), other.clone()) };
         match compared {
-            c if c < 0 => Some(Ordering::Less),
-            c if c == 0 => Some(Ordering::Equal),
-            c if c > 0 => Some(Ordering::Greater),
-            _ => None,
+            TYPE_UNORDERED => None,
+            c => Some(c.cmp(&0)),
         }
     }
 }
 
-impl PartialEq for ObjectType {
-    fn eq(&self, other: &Self) -> bool {
-        match self.partial_cmp(other) {
-            Some(Ordering::Equal) => true,
-            _ => false,
-        }
+impl PartialOrd for ObjectType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.compare_inclusion(other)
     }
 }
 
@@ -132,6 +147,7 @@ const TOPOLOGY_FLAG_WHOLE_IO: i64 = 16;
 const TOPOLOGY_FLAG_I_CACHES: i64 = 32;
 
 #[derive(Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TopologyFlag {
     WholeSystem = TOPOLOGY_FLAG_WHOLE_SYSTEM as isize,
     IsThisSystem = TOPOLOGY_FLAG_IS_THIS_SYSTEM as isize,
@@ -190,7 +206,39 @@ extern "C" {
 
     pub fn hwloc_topology_set_flags(topology: *mut HwlocTopology, flags: c_ulonglong) -> c_int;
     pub fn hwloc_topology_get_flags(topology: *mut HwlocTopology) -> c_ulonglong;
+    pub fn hwloc_topology_ignore_type(topology: *mut HwlocTopology, object_type: ObjectType) -> c_int;
+    pub fn hwloc_topology_ignore_type_keep_structure(topology: *mut HwlocTopology,
+                                                     object_type: ObjectType)
+                                                     -> c_int;
+    pub fn hwloc_topology_ignore_all_keep_structure(topology: *mut HwlocTopology) -> c_int;
     pub fn hwloc_topology_get_support(topology: *mut HwlocTopology) -> *const TopologySupport;
+    pub fn hwloc_topology_is_thissystem(topology: *mut HwlocTopology) -> c_int;
+    pub fn hwloc_topology_set_pid(topology: *mut HwlocTopology, pid: pid_t) -> c_int;
+    pub fn hwloc_topology_set_fsroot(topology: *mut HwlocTopology,
+                                     fsroot_path: *const c_char)
+                                     -> c_int;
+
+    // === Exporting and Importing Topologies ===
+
+    pub fn hwloc_topology_set_xml(topology: *mut HwlocTopology, xmlpath: *const c_char) -> c_int;
+    pub fn hwloc_topology_set_xmlbuffer(topology: *mut HwlocTopology,
+                                        buffer: *const c_char,
+                                        size: c_int)
+                                        -> c_int;
+    pub fn hwloc_topology_export_xml(topology: *mut HwlocTopology, xmlpath: *const c_char) -> c_int;
+    pub fn hwloc_topology_export_xmlbuffer(topology: *mut HwlocTopology,
+                                           xmlbuffer: *mut *mut c_char,
+                                           buflen: *mut c_int)
+                                           -> c_int;
+    pub fn hwloc_free_xmlbuffer(topology: *mut HwlocTopology, xmlbuffer: *mut c_char);
+    pub fn hwloc_topology_set_synthetic(topology: *mut HwlocTopology,
+                                        description: *const c_char)
+                                        -> c_int;
+    pub fn hwloc_topology_export_synthetic(topology: *mut HwlocTopology,
+                                           buffer: *mut c_char,
+                                           buflen: size_t,
+                                           flags: c_ulong)
+                                           -> c_int;
 
     // === Object levels, depths and types ===
 
@@ -251,9 +299,18 @@ extern "C" {
     pub fn hwloc_bitmap_alloc() -> *mut IntHwlocBitmap;
     pub fn hwloc_bitmap_alloc_full() -> *mut IntHwlocBitmap;
     pub fn hwloc_bitmap_free(bitmap: *mut IntHwlocBitmap);
+    pub fn hwloc_bitmap_asprintf(strp: *mut *mut c_char, bitmap: *const IntHwlocBitmap) -> c_int;
+    pub fn hwloc_bitmap_sscanf(bitmap: *mut IntHwlocBitmap, string: *const c_char) -> c_int;
     pub fn hwloc_bitmap_list_asprintf(strp: *mut *mut c_char,
                                       bitmap: *const IntHwlocBitmap)
                                       -> c_int;
+    pub fn hwloc_bitmap_list_sscanf(bitmap: *mut IntHwlocBitmap, string: *const c_char) -> c_int;
+    pub fn hwloc_bitmap_taskset_asprintf(strp: *mut *mut c_char,
+                                         bitmap: *const IntHwlocBitmap)
+                                         -> c_int;
+    pub fn hwloc_bitmap_taskset_sscanf(bitmap: *mut IntHwlocBitmap,
+                                       string: *const c_char)
+                                       -> c_int;
     pub fn hwloc_bitmap_set(bitmap: *mut IntHwlocBitmap, id: c_uint);
     pub fn hwloc_bitmap_set_range(bitmap: *mut IntHwlocBitmap, begin: c_uint, end: c_int);
     pub fn hwloc_bitmap_clr(bitmap: *mut IntHwlocBitmap, id: c_uint);
@@ -275,6 +332,29 @@ extern "C" {
                                 -> c_int;
     pub fn hwloc_bitmap_isfull(bitmap: *const IntHwlocBitmap) -> c_int;
     pub fn hwloc_bitmap_next(bitmap: *const IntHwlocBitmap, prev: c_int) -> c_int;
+    pub fn hwloc_bitmap_to_ith_ulong(bitmap: *const IntHwlocBitmap, i: c_uint) -> c_ulong;
+    pub fn hwloc_bitmap_set_ith_ulong(bitmap: *mut IntHwlocBitmap, i: c_uint, mask: c_ulong);
+    pub fn hwloc_bitmap_and(result: *mut IntHwlocBitmap,
+                            left: *const IntHwlocBitmap,
+                            right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_or(result: *mut IntHwlocBitmap,
+                           left: *const IntHwlocBitmap,
+                           right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_xor(result: *mut IntHwlocBitmap,
+                            left: *const IntHwlocBitmap,
+                            right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_andnot(result: *mut IntHwlocBitmap,
+                               left: *const IntHwlocBitmap,
+                               right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_intersects(left: *const IntHwlocBitmap,
+                                   right: *const IntHwlocBitmap)
+                                   -> c_int;
+    pub fn hwloc_bitmap_isincluded(sub_bitmap: *const IntHwlocBitmap,
+                                   super_bitmap: *const IntHwlocBitmap)
+                                   -> c_int;
+    pub fn hwloc_bitmap_compare_first(left: *const IntHwlocBitmap,
+                                      right: *const IntHwlocBitmap)
+                                      -> c_int;
 
     pub fn hwloc_obj_type_snprintf(into: *mut c_char,
                                    size: c_int,
@@ -305,7 +385,39 @@ extern "C" {
 
     pub fn hwloc_topology_set_flags(topology: *mut HwlocTopology, flags: c_ulonglong) -> c_int;
     pub fn hwloc_topology_get_flags(topology: *mut HwlocTopology) -> c_ulonglong;
+    pub fn hwloc_topology_ignore_type(topology: *mut HwlocTopology, object_type: ObjectType) -> c_int;
+    pub fn hwloc_topology_ignore_type_keep_structure(topology: *mut HwlocTopology,
+                                                     object_type: ObjectType)
+                                                     -> c_int;
+    pub fn hwloc_topology_ignore_all_keep_structure(topology: *mut HwlocTopology) -> c_int;
     pub fn hwloc_topology_get_support(topology: *mut HwlocTopology) -> *const TopologySupport;
+    pub fn hwloc_topology_is_thissystem(topology: *mut HwlocTopology) -> c_int;
+    pub fn hwloc_topology_set_pid(topology: *mut HwlocTopology, pid: pid_t) -> c_int;
+    pub fn hwloc_topology_set_fsroot(topology: *mut HwlocTopology,
+                                     fsroot_path: *const c_char)
+                                     -> c_int;
+
+    // === Exporting and Importing Topologies ===
+
+    pub fn hwloc_topology_set_xml(topology: *mut HwlocTopology, xmlpath: *const c_char) -> c_int;
+    pub fn hwloc_topology_set_xmlbuffer(topology: *mut HwlocTopology,
+                                        buffer: *const c_char,
+                                        size: c_int)
+                                        -> c_int;
+    pub fn hwloc_topology_export_xml(topology: *mut HwlocTopology, xmlpath: *const c_char) -> c_int;
+    pub fn hwloc_topology_export_xmlbuffer(topology: *mut HwlocTopology,
+                                           xmlbuffer: *mut *mut c_char,
+                                           buflen: *mut c_int)
+                                           -> c_int;
+    pub fn hwloc_free_xmlbuffer(topology: *mut HwlocTopology, xmlbuffer: *mut c_char);
+    pub fn hwloc_topology_set_synthetic(topology: *mut HwlocTopology,
+                                        description: *const c_char)
+                                        -> c_int;
+    pub fn hwloc_topology_export_synthetic(topology: *mut HwlocTopology,
+                                           buffer: *mut c_char,
+                                           buflen: size_t,
+                                           flags: c_ulong)
+                                           -> c_int;
 
     // === Object levels, depths and types ===
 
@@ -362,20 +474,68 @@ extern "C" {
     // === Memory Binding === Added by long 20210707
     pub fn hwloc_set_membind(topology: *mut HwlocTopology,
                              set: *const IntHwlocBitmap,
-                             policy: MemBindPolicy,
+                             policy: c_int,
                              flags: c_int) -> c_int;
     pub fn hwloc_get_membind(topology: *mut HwlocTopology,
                              set: *mut IntHwlocBitmap,
-                             policy: *mut MemBindPolicy,
+                             policy: *mut c_int,
                              flags: c_int) -> c_int;
+    pub fn hwloc_set_proc_membind(topology: *mut HwlocTopology,
+                                  pid: pid_t,
+                                  set: *const IntHwlocBitmap,
+                                  policy: c_int,
+                                  flags: c_int)
+                                  -> c_int;
+    pub fn hwloc_get_proc_membind(topology: *mut HwlocTopology,
+                                  pid: pid_t,
+                                  set: *mut IntHwlocBitmap,
+                                  policy: *mut c_int,
+                                  flags: c_int)
+                                  -> c_int;
+    pub fn hwloc_set_area_membind(topology: *mut HwlocTopology,
+                                  addr: *const c_void,
+                                  len: size_t,
+                                  set: *const IntHwlocBitmap,
+                                  policy: c_int,
+                                  flags: c_int)
+                                  -> c_int;
+    pub fn hwloc_get_area_membind(topology: *mut HwlocTopology,
+                                  addr: *const c_void,
+                                  len: size_t,
+                                  set: *mut IntHwlocBitmap,
+                                  policy: *mut c_int,
+                                  flags: c_int)
+                                  -> c_int;
+    pub fn hwloc_get_area_memlocation(topology: *mut HwlocTopology,
+                                      addr: *const c_void,
+                                      len: size_t,
+                                      set: *mut IntHwlocBitmap,
+                                      flags: c_int)
+                                      -> c_int;
+    pub fn hwloc_alloc_membind(topology: *mut HwlocTopology,
+                               len: size_t,
+                               set: *const IntHwlocBitmap,
+                               policy: c_int,
+                               flags: c_int)
+                               -> *mut c_void;
+    pub fn hwloc_free(topology: *mut HwlocTopology, addr: *mut c_void, len: size_t) -> c_int;
 
     // === Bitmap Methods ===
     pub fn hwloc_bitmap_alloc() -> *mut IntHwlocBitmap;
     pub fn hwloc_bitmap_alloc_full() -> *mut IntHwlocBitmap;
     pub fn hwloc_bitmap_free(bitmap: *mut IntHwlocBitmap);
+    pub fn hwloc_bitmap_asprintf(strp: *mut *mut c_char, bitmap: *const IntHwlocBitmap) -> c_int;
+    pub fn hwloc_bitmap_sscanf(bitmap: *mut IntHwlocBitmap, string: *const c_char) -> c_int;
     pub fn hwloc_bitmap_list_asprintf(strp: *mut *mut c_char,
                                       bitmap: *const IntHwlocBitmap)
                                       -> c_int;
+    pub fn hwloc_bitmap_list_sscanf(bitmap: *mut IntHwlocBitmap, string: *const c_char) -> c_int;
+    pub fn hwloc_bitmap_taskset_asprintf(strp: *mut *mut c_char,
+                                         bitmap: *const IntHwlocBitmap)
+                                         -> c_int;
+    pub fn hwloc_bitmap_taskset_sscanf(bitmap: *mut IntHwlocBitmap,
+                                       string: *const c_char)
+                                       -> c_int;
     pub fn hwloc_bitmap_set(bitmap: *mut IntHwlocBitmap, id: c_uint);
     pub fn hwloc_bitmap_set_range(bitmap: *mut IntHwlocBitmap, begin: c_uint, end: c_int);
     pub fn hwloc_bitmap_clr(bitmap: *mut IntHwlocBitmap, id: c_uint);
@@ -397,6 +557,29 @@ extern "C" {
                                 -> c_int;
     pub fn hwloc_bitmap_isfull(bitmap: *const IntHwlocBitmap) -> c_int;
     pub fn hwloc_bitmap_next(bitmap: *const IntHwlocBitmap, prev: c_int) -> c_int;
+    pub fn hwloc_bitmap_to_ith_ulong(bitmap: *const IntHwlocBitmap, i: c_uint) -> c_ulong;
+    pub fn hwloc_bitmap_set_ith_ulong(bitmap: *mut IntHwlocBitmap, i: c_uint, mask: c_ulong);
+    pub fn hwloc_bitmap_and(result: *mut IntHwlocBitmap,
+                            left: *const IntHwlocBitmap,
+                            right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_or(result: *mut IntHwlocBitmap,
+                           left: *const IntHwlocBitmap,
+                           right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_xor(result: *mut IntHwlocBitmap,
+                            left: *const IntHwlocBitmap,
+                            right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_andnot(result: *mut IntHwlocBitmap,
+                               left: *const IntHwlocBitmap,
+                               right: *const IntHwlocBitmap);
+    pub fn hwloc_bitmap_intersects(left: *const IntHwlocBitmap,
+                                   right: *const IntHwlocBitmap)
+                                   -> c_int;
+    pub fn hwloc_bitmap_isincluded(sub_bitmap: *const IntHwlocBitmap,
+                                   super_bitmap: *const IntHwlocBitmap)
+                                   -> c_int;
+    pub fn hwloc_bitmap_compare_first(left: *const IntHwlocBitmap,
+                                      right: *const IntHwlocBitmap)
+                                      -> c_int;
 
     pub fn hwloc_obj_type_snprintf(into: *mut c_char,
                                    size: c_int,
@@ -433,4 +616,22 @@ mod tests {
         assert!(ObjectType::PU > ObjectType::Cache);
     }
 
+    #[test]
+    fn should_not_order_io_object_types() {
+        assert_eq!(None, ObjectType::PU.partial_cmp(&ObjectType::OSDevice));
+        assert_eq!(None, ObjectType::OSDevice.partial_cmp(&ObjectType::PU));
+        assert_eq!(None, ObjectType::OSDevice.compare_inclusion(&ObjectType::PU));
+    }
+
+    #[test]
+    fn should_hash_object_types() {
+        use std::collections::HashSet;
+
+        let types: HashSet<ObjectType> =
+            vec![ObjectType::Core, ObjectType::PU, ObjectType::Core].into_iter().collect();
+        assert_eq!(2, types.len());
+        assert!(types.contains(&ObjectType::PU));
+        assert!(!types.contains(&ObjectType::Cache));
+    }
+
 }
@@ -0,0 +1,87 @@
+//! Conversions between `CpuSet` and the `libc::cpu_set_t` of `sched_setaffinity`.
+//!
+//! A `cpu_set_t` is an array of `unsigned long` masks, so it is converted through
+//! `Bitmap::from_ulongs` and `Bitmap::to_ulongs`. Those also handle the dynamically
+//! sized masks of `CPU_ALLOC` for machines with more than `CPU_SETSIZE` CPUs.
+
+use std::convert::TryFrom;
+use std::{mem, slice};
+use libc::{c_ulong, cpu_set_t};
+
+use bitmap::{Bitmap, BitmapConversionError, CpuSet};
+
+const ULONGS: usize = mem::size_of::<cpu_set_t>() / mem::size_of::<c_ulong>();
+
+impl<'a> From<&'a cpu_set_t> for CpuSet {
+    fn from(set: &'a cpu_set_t) -> CpuSet {
+        let ulongs = unsafe { slice::from_raw_parts(set as *const _ as *const c_ulong, ULONGS) };
+        CpuSet::from_bitmap(Bitmap::from_ulongs(ulongs))
+    }
+}
+
+impl From<cpu_set_t> for CpuSet {
+    fn from(set: cpu_set_t) -> CpuSet {
+        (&set).into()
+    }
+}
+
+impl<'a> TryFrom<&'a CpuSet> for cpu_set_t {
+    type Error = BitmapConversionError;
+
+    /// Fails if `set` is infinite or contains a CPU beyond `CPU_SETSIZE`.
+    fn try_from(set: &'a CpuSet) -> Result<cpu_set_t, BitmapConversionError> {
+        let ulongs = set.to_ulongs()?;
+        if ulongs.len() > ULONGS {
+            return Err(BitmapConversionError::IndexTooLarge(set.last() as u32));
+        }
+
+        let mut result: cpu_set_t = unsafe { mem::zeroed() };
+        let target =
+            unsafe { slice::from_raw_parts_mut(&mut result as *mut _ as *mut c_ulong, ULONGS) };
+        target[..ulongs.len()].copy_from_slice(&ulongs);
+        Ok(result)
+    }
+}
+
+impl TryFrom<CpuSet> for cpu_set_t {
+    type Error = BitmapConversionError;
+
+    fn try_from(set: CpuSet) -> Result<cpu_set_t, BitmapConversionError> {
+        cpu_set_t::try_from(&set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use libc;
+
+    #[test]
+    fn should_convert_to_and_from_cpu_set_t() {
+        let mut set: cpu_set_t = unsafe { mem::zeroed() };
+        unsafe {
+            libc::CPU_SET(1, &mut set);
+            libc::CPU_SET(70, &mut set);
+        }
+
+        let cpuset: CpuSet = (&set).into();
+        assert_eq!("1,70", cpuset.to_string());
+
+        let converted = cpu_set_t::try_from(&cpuset).unwrap();
+        assert!(unsafe { libc::CPU_ISSET(70, &converted) });
+        assert!(unsafe { !libc::CPU_ISSET(2, &converted) });
+        let round_tripped: CpuSet = converted.into();
+        assert_eq!(cpuset, round_tripped);
+    }
+
+    #[test]
+    fn should_reject_sets_not_fitting_cpu_set_t() {
+        let capacity = (ULONGS * mem::size_of::<c_ulong>() * 8) as u32;
+
+        assert_eq!(Some(BitmapConversionError::IndexTooLarge(capacity)),
+                   cpu_set_t::try_from(CpuSet::from(capacity)).err());
+        assert_eq!(Some(BitmapConversionError::Infinite),
+                   cpu_set_t::try_from(CpuSet::full()).err());
+    }
+}
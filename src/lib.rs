@@ -79,27 +79,54 @@
 
 #![allow(deprecated)]
 #![allow(dead_code)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 #[macro_use]
 extern crate bitflags;
 extern crate errno;
 extern crate libc;
 extern crate num;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 #[cfg(target_os = "windows")]
 extern crate winapi;
 
 mod ffi;
 mod topology_object;
 mod bitmap;
+mod pure_bitmap;
 mod support;
+mod builder;
+mod snapshot;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(target_os = "linux")]
+mod glibc_sched;
+mod buffer;
+mod migration;
+#[cfg(feature = "numa-allocator")]
+pub mod alloc;
 
 pub use ffi::{ObjectType, TypeDepthError, TopologyFlag};
-pub use bitmap::{Bitmap, CpuSet, NodeSet};
+pub use bitmap::{Bitmap, BitmapRef, CpuSet, NodeSet, ParseBitmapError, BitmapConversionError,
+                 BitmapIntoIterator, BitmapIter, BitmapFiniteIter, BitmapRevIter, BitmapRanges,
+                 BitmapUnsetIter};
+pub use pure_bitmap::{PureBitmap, PureBitmapIter, PureBitmapRanges};
 pub use support::{TopologySupport, TopologyDiscoverySupport, TopologyCpuBindSupport,
                   TopologyMemBindSupport};
-pub use topology_object::{TopologyObject, TopologyObjectMemory};
-
-use num::{ToPrimitive, FromPrimitive};
+pub use topology_object::{TopologyObject, TopologyObjectMemory, TopologyObjectCacheAttributes,
+                          Associativity, CacheType};
+pub use builder::TopologyBuilder;
+pub use snapshot::{ObjectSnapshot, CacheSnapshot};
+pub use buffer::{NumaBuffer, NumaBox, NumaVec};
+pub use migration::MigrationReport;
+
+use num::FromPrimitive;
 use errno::errno;
+use libc::{c_char, c_int, c_void};
+use std::ffi::CStr;
+use std::path::Path;
+use builder::path_to_cstring;
 
 pub struct Topology {
     topo: *mut ffi::HwlocTopology,
@@ -140,20 +167,13 @@ impl Topology {
     ///
     /// Note that the topology implements the Drop trait, so when
     /// it goes out of scope no further cleanup is necessary.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if hwloc fails to initialize or load the topology.
+    /// Use the `TopologyBuilder` to handle such failures gracefully.
     pub fn new() -> Topology {
-        let mut topo: *mut ffi::HwlocTopology = std::ptr::null_mut();
-
-        unsafe {
-            ffi::hwloc_topology_init(&mut topo);
-            ffi::hwloc_topology_load(topo);
-        }
-
-        let support = unsafe { ffi::hwloc_topology_get_support(topo) };
-
-        Topology {
-            topo: topo,
-            support: support,
-        }
+        TopologyBuilder::new().build().expect("Failed to load the topology of this system")
     }
 
     /// Creates a new Topology with custom flags.
@@ -171,26 +191,103 @@ impl Topology {
     ///
     /// Note that the topology implements the Drop trait, so when
     /// it goes out of scope no further cleanup is necessary.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if hwloc fails to initialize or load the topology.
+    /// Use the `TopologyBuilder` to handle such failures gracefully.
     pub fn with_flags(flags: Vec<TopologyFlag>) -> Topology {
-        let mut topo: *mut ffi::HwlocTopology = std::ptr::null_mut();
+        flags.into_iter()
+            .fold(TopologyBuilder::new(), |builder, flag| builder.flag(flag))
+            .build()
+            .expect("Failed to load the topology of this system")
+    }
 
-        let final_flag = flags
-            .iter()
-            .map(|f| f.to_u64().unwrap())
-            .fold(0, |out, current| out | current);
+    /// Creates a new Topology from a previously exported XML file.
+    ///
+    /// Instead of discovering the hardware of the current machine, the topology
+    /// is loaded from the XML file at `path`, usually produced by `export_xml` or
+    /// by the `lstopo` tool on another host.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hwloc::Topology;
+    ///
+    /// let topology = Topology::from_xml_file("/tmp/topology.xml").unwrap();
+    /// assert!(topology.depth() > 0);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// Returns a `TopologyError` if the path can't be handed to hwloc or if
+    /// the file can't be read or parsed.
+    pub fn from_xml_file<P: AsRef<Path>>(path: P) -> Result<Topology, TopologyError> {
+        TopologyBuilder::new().xml_file(path).build()
+    }
 
-        unsafe {
-            ffi::hwloc_topology_init(&mut topo);
-            ffi::hwloc_topology_set_flags(topo, final_flag);
-            ffi::hwloc_topology_load(topo);
-        }
+    /// Creates a new Topology from an in-memory XML description.
+    ///
+    /// This works like `from_xml_file`, but takes the XML content itself, for
+    /// example the output of `export_xml_buffer`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::Topology;
+    ///
+    /// let xml = Topology::new().export_xml_buffer().unwrap();
+    /// let topology = Topology::from_xml_buffer(&xml).unwrap();
+    /// assert!(topology.depth() > 0);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// Returns a `TopologyError` if the buffer contains a nul byte or can't be
+    /// parsed by hwloc.
+    pub fn from_xml_buffer(xml: &str) -> Result<Topology, TopologyError> {
+        TopologyBuilder::new().xml_buffer(xml).build()
+    }
 
-        let support = unsafe { ffi::hwloc_topology_get_support(topo) };
+    /// Creates a new synthetic Topology from a description string.
+    ///
+    /// A synthetic topology does not reflect the current machine, it is built from a
+    /// space-separated list of levels with their arity, like `"node:2 pack:2 core:8 pu:2"`.
+    /// This is mostly useful to test code against a deterministic topology.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:4 pu:2").unwrap();
+    /// assert_eq!(16, topology.objects_with_type(&ObjectType::PU).unwrap().len());
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// Returns a `TopologyError` if hwloc can't parse the description.
+    pub fn from_synthetic(description: &str) -> Result<Topology, TopologyError> {
+        TopologyBuilder::new().synthetic(description).build()
+    }
 
-        Topology {
-            topo: topo,
-            support: support,
-        }
+    /// Checks whether the topology describes the system this process is running on.
+    ///
+    /// Topologies loaded from XML, a synthetic description or another filesystem
+    /// root usually describe a different machine. Binding calls on such a
+    /// topology would not do what the caller expects, so they should be refused
+    /// unless this returns `true`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::Topology;
+    ///
+    /// assert!(Topology::new().is_this_system());
+    /// assert!(!Topology::from_synthetic("pack:2 core:2").unwrap().is_this_system());
+    /// ```
+    pub fn is_this_system(&self) -> bool {
+        unsafe { ffi::hwloc_topology_is_thissystem(self.topo) == 1 }
     }
 
     pub fn support(&self) -> &TopologySupport {
@@ -223,6 +320,116 @@ impl Topology {
             .collect::<Vec<TopologyFlag>>()
     }
 
+    /// Exports the topology into an XML file at `path`.
+    ///
+    /// The file can later be loaded on any host through `Topology::from_xml_file`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hwloc::Topology;
+    ///
+    /// let topology = Topology::new();
+    /// topology.export_xml("/tmp/topology.xml").unwrap();
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// Returns `TopologyError::Export` if hwloc fails to write the file.
+    pub fn export_xml<P: AsRef<Path>>(&self, path: P) -> Result<(), TopologyError> {
+        let path = path_to_cstring(path.as_ref())?;
+        let result = unsafe { ffi::hwloc_topology_export_xml(self.topo, path.as_ptr()) };
+
+        match result {
+            r if r < 0 => {
+                let e = errno();
+                Err(TopologyError::Export(e.0 as i32, format!("{}", e)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Exports the topology into an XML string.
+    ///
+    /// The string can later be loaded through `Topology::from_xml_buffer`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::Topology;
+    ///
+    /// let topology = Topology::new();
+    /// let xml = topology.export_xml_buffer().unwrap();
+    /// assert!(xml.contains("<topology>"));
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// Returns `TopologyError::Export` if hwloc fails to produce the buffer.
+    pub fn export_xml_buffer(&self) -> Result<String, TopologyError> {
+        let mut buffer: *mut c_char = std::ptr::null_mut();
+        let mut len: c_int = 0;
+        let result =
+            unsafe { ffi::hwloc_topology_export_xmlbuffer(self.topo, &mut buffer, &mut len) };
+
+        if result < 0 || buffer.is_null() {
+            let e = errno();
+            return Err(TopologyError::Export(e.0 as i32, format!("{}", e)));
+        }
+
+        unsafe {
+            let xml = CStr::from_ptr(buffer).to_string_lossy().into_owned();
+            ffi::hwloc_free_xmlbuffer(self.topo, buffer);
+            Ok(xml)
+        }
+    }
+
+    /// Exports the topology as a synthetic description string.
+    ///
+    /// The result can be passed to `Topology::from_synthetic` to rebuild a
+    /// topology with the same structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::Topology;
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    /// let description = topology.export_synthetic().unwrap();
+    ///
+    /// let rebuilt = Topology::from_synthetic(&description).unwrap();
+    /// assert_eq!(topology.depth(), rebuilt.depth());
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// Only symmetric topologies can be exported, otherwise
+    /// `TopologyError::Export` is returned.
+    pub fn export_synthetic(&self) -> Result<String, TopologyError> {
+        let mut buffer: Vec<u8> = vec![0; 1024];
+        loop {
+            let result = unsafe {
+                ffi::hwloc_topology_export_synthetic(self.topo,
+                                                     buffer.as_mut_ptr() as *mut c_char,
+                                                     buffer.len(),
+                                                     0)
+            };
+
+            if result < 0 {
+                let e = errno();
+                return Err(TopologyError::Export(e.0 as i32, format!("{}", e)));
+            }
+
+            // Like snprintf, hwloc returns the length it would have needed.
+            let needed = result as usize;
+            if needed < buffer.len() {
+                buffer.truncate(needed);
+                return Ok(String::from_utf8_lossy(&buffer).into_owned());
+            }
+            buffer.resize(needed + 1, 0);
+        }
+    }
+
     /// Returns the full depth of the topology.
     ///
     /// In practice, the full depth of the topology equals the depth of the `ObjectType::PU`
@@ -382,6 +589,26 @@ impl Topology {
         self.objects_at_depth(0).first().unwrap()
     }
 
+    /// Takes an owned snapshot of the whole object tree, starting at the root object.
+    ///
+    /// With the `serde` feature enabled the snapshot can be serialized, for example to
+    /// send the topology of this machine to another one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::Topology;
+    ///
+    /// let topology = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+    /// let snapshot = topology.snapshot();
+    ///
+    /// assert_eq!(2, snapshot.children.len());
+    /// assert_eq!("4-7", snapshot.children[1].cpuset.as_ref().unwrap().to_string());
+    /// ```
+    pub fn snapshot(&self) -> ObjectSnapshot {
+        ObjectSnapshot::new(self.object_at_root())
+    }
+
     /// Returns the `ObjectType` at the root of the topology.
     ///
     /// This method is a convenient shorthand for `type_at_depth(0)`.
@@ -428,109 +655,465 @@ impl Topology {
             .collect::<Vec<&TopologyObject>>()
     }
 
-    /// Binds the current process or thread on CPUs given in the `CpuSet`.
-    pub fn set_cpubind(&mut self, set: CpuSet, flags: CpuBindFlags) -> Result<(), CpuBindError> {
-        let result = unsafe { ffi::hwloc_set_cpubind(self.topo, set.as_ptr(), flags.bits()) };
-
-        match result {
-            r if r < 0 => {
-                let e = errno();
-                Err(CpuBindError::Generic(e.0 as i32, format!("{}", e)))
-            }
-            _ => Ok(()),
+    /// Returns the object of the given type with the given logical index.
+    ///
+    /// Unlike `objects_at_depth`, this never panics and returns `None` if the
+    /// type doesn't exist in the topology, exists at multiple depths or if
+    /// `idx` is out of range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    ///
+    /// let core = topology.object_by_type_and_index(&ObjectType::Core, 3).unwrap();
+    /// assert_eq!(3, core.logical_index());
+    /// assert!(topology.object_by_type_and_index(&ObjectType::Core, 4).is_none());
+    /// ```
+    pub fn object_by_type_and_index(&self,
+                                    object_type: &ObjectType,
+                                    idx: u32)
+                                    -> Option<&TopologyObject> {
+        let depth = match self.depth_for_type(object_type) {
+            Ok(depth) => depth,
+            Err(TypeDepthError::TypeDepthBridge) => TypeDepthError::TypeDepthBridge as u32,
+            Err(TypeDepthError::TypeDepthPCIDevice) => TypeDepthError::TypeDepthPCIDevice as u32,
+            Err(TypeDepthError::TypeDepthOSDevice) => TypeDepthError::TypeDepthOSDevice as u32,
+            Err(_) => return None,
+        };
+
+        let object = unsafe { ffi::hwloc_get_obj_by_depth(self.topo, depth, idx) };
+        if object.is_null() {
+            None
+        } else {
+            Some(unsafe { &*object })
         }
     }
 
-    /// Get current process or thread binding.
-    pub fn get_cpubind(&self, flags: CpuBindFlags) -> Option<CpuSet> {
-        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
-        let res = unsafe { ffi::hwloc_get_cpubind(self.topo, raw_set, flags.bits()) };
-        if res >= 0 {
-            Some(CpuSet::from_raw(raw_set, true))
-        } else {
-            None
-        }
+    /// Returns the PU with the given OS index.
+    ///
+    /// This is the number the operating system uses for the processor, for example
+    /// the one returned by `sched_getcpu` on Linux.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType};
+    ///
+    /// let topology = Topology::new();
+    ///
+    /// let pu = topology.pu_by_os_index(0).unwrap();
+    /// assert_eq!(ObjectType::PU, pu.object_type());
+    /// assert_eq!(0, pu.os_index());
+    /// ```
+    pub fn pu_by_os_index(&self, os_index: u32) -> Option<&TopologyObject> {
+        self.object_by_type_and_os_index(&ObjectType::PU, os_index)
     }
 
-    /// Binds a process (identified by its `pid`) on CPUs identified by the given `CpuSet`.
-    pub fn set_cpubind_for_process(&mut self,
-                                   pid: pid_t,
-                                   set: CpuSet,
-                                   flags: CpuBindFlags)
-                                   -> Result<(), CpuBindError> {
-        let result =
-            unsafe { ffi::hwloc_set_proc_cpubind(self.topo, pid, set.as_ptr(), flags.bits()) };
+    /// Returns the NUMA node with the given OS index.
+    ///
+    /// Returns `None` if there is no such node, which includes machines where
+    /// hwloc does not report any NUMA node at all.
+    pub fn numa_node_by_os_index(&self, os_index: u32) -> Option<&TopologyObject> {
+        self.object_by_type_and_os_index(&ObjectType::NUMANode, os_index)
+    }
 
-        match result {
-            r if r < 0 => {
-                let e = errno();
-                Err(CpuBindError::Generic(e.0 as i32, format!("{}", e)))
+    fn object_by_type_and_os_index(&self,
+                                   object_type: &ObjectType,
+                                   os_index: u32)
+                                   -> Option<&TopologyObject> {
+        let mut next = self.object_by_type_and_index(object_type, 0);
+        while let Some(object) = next {
+            if object.os_index() == os_index {
+                return Some(object);
             }
-            _ => Ok(()),
+            next = object.next_cousin();
         }
+        None
     }
 
-    /// Get the current physical binding of a process, identified by its `pid`.
-    pub fn get_cpubind_for_process(&self, pid: pid_t, flags: CpuBindFlags) -> Option<CpuSet> {
-        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
-        let res = unsafe { ffi::hwloc_get_proc_cpubind(self.topo, pid, raw_set, flags.bits()) };
-        if res >= 0 {
-            Some(CpuSet::from_raw(raw_set, true))
-        } else {
-            None
+    /// Returns the smallest object covering the given `CpuSet`.
+    ///
+    /// This is the deepest object whose CPU set includes all indexes of `set`.
+    /// Returns `None` if `set` is empty or isn't covered by the root object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType, CpuSet};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    ///
+    /// let core = topology.object_covering_cpuset(&CpuSet::from_range(0, 1)).unwrap();
+    /// assert_eq!(ObjectType::Core, core.object_type());
+    ///
+    /// let package = topology.object_covering_cpuset(&CpuSet::from_range(0, 3)).unwrap();
+    /// assert_eq!(ObjectType::Package, package.object_type());
+    /// ```
+    pub fn object_covering_cpuset(&self, set: &CpuSet) -> Option<&TopologyObject> {
+        let mut current = self.object_at_root();
+        if set.is_empty() || !covers(current, set) {
+            return None;
         }
-    }
-
-    /// Bind a thread (by its `tid`) on CPUs given in through the `CpuSet`.
-    pub fn set_cpubind_for_thread(&mut self,
-                                  tid: pthread_t,
-                                  set: CpuSet,
-                                  flags: CpuBindFlags)
-                                  -> Result<(), CpuBindError> {
-        let result =
-            unsafe { ffi::hwloc_set_thread_cpubind(self.topo, tid, set.as_ptr(), flags.bits()) };
 
-        match result {
-            r if r < 0 => {
-                let e = errno();
-                Err(CpuBindError::Generic(e.0 as i32, format!("{}", e)))
+        loop {
+            match current.children().into_iter().find(|child| covers(child, set)) {
+                Some(child) => current = child,
+                None => return Some(current),
             }
-            _ => Ok(()),
         }
     }
 
-    /// Get the current physical binding of thread `tid`.
-    pub fn get_cpubind_for_thread(&self, tid: pthread_t, flags: CpuBindFlags) -> Option<CpuSet> {
-        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
-        let res = unsafe { ffi::hwloc_get_thread_cpubind(self.topo, tid, raw_set, flags.bits()) };
-        if res >= 0 {
-            Some(CpuSet::from_raw(raw_set, true))
-        } else {
-            None
+    /// Returns all objects of the given type which are included in the `CpuSet`.
+    ///
+    /// Objects with an empty CPU set are skipped. If no depth exists for the type
+    /// (for example because objects of that type live at multiple depths), an
+    /// empty vector is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType, CpuSet};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    ///
+    /// let cores = topology.objects_inside_cpuset_by_type(&CpuSet::from_range(0, 5),
+    ///                                                    &ObjectType::Core);
+    /// assert_eq!(3, cores.len());
+    /// ```
+    pub fn objects_inside_cpuset_by_type(&self,
+                                         set: &CpuSet,
+                                         object_type: &ObjectType)
+                                         -> Vec<&TopologyObject> {
+        match self.depth_for_type(object_type) {
+            Ok(depth) => {
+                self.objects_at_depth(depth)
+                    .into_iter()
+                    .filter(|object| match object.cpuset() {
+                        Some(cpuset) => !cpuset.is_empty() && cpuset.is_included_in(set),
+                        None => false,
+                    })
+                    .collect::<Vec<&TopologyObject>>()
+            }
+            Err(_) => Vec::new(),
         }
     }
 
-    /// Get the last physical CPU where the current process or thread ran.
+    /// Returns the first largest object included in the `CpuSet`.
     ///
-    /// The operating system may move some tasks from one processor
-    /// to another at any time according to their binding,
-    /// so this function may return something that is already
-    /// outdated.
+    /// Starting from the root, the tree is descended into the first child
+    /// intersecting `set` until an object whose CPU set is included in `set` is
+    /// found. Returns `None` if `set` doesn't intersect the root object.
     ///
-    /// Flags can include either `CPUBIND_PROCESS` or `CPUBIND_THREAD` to
-    /// specify whether the query should be for the whole process (union of all CPUs
-    /// on which all threads are running), or only the current thread. If the
-    /// process is single-threaded, flags can be set to zero to let hwloc use
-    /// whichever method is available on the underlying OS.
-    pub fn get_cpu_location(&self, flags: CpuBindFlags) -> Option<CpuSet> {
-        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
-        let res = unsafe { ffi::hwloc_get_last_cpu_location(self.topo, raw_set, flags.bits()) };
-        if res >= 0 {
-            Some(CpuSet::from_raw(raw_set, true))
-        } else {
-            None
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType, CpuSet};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    ///
+    /// let largest = topology.first_largest_object_inside_cpuset(&CpuSet::from_range(0, 5))
+    ///     .unwrap();
+    /// assert_eq!(ObjectType::Package, largest.object_type());
+    /// ```
+    pub fn first_largest_object_inside_cpuset(&self, set: &CpuSet) -> Option<&TopologyObject> {
+        let mut current = self.object_at_root();
+        if !intersects(current, set) {
+            return None;
         }
-    }
+
+        while !matches!(current.cpuset(), Some(cpuset) if cpuset.is_included_in(set)) {
+            match current.children().into_iter().find(|child| intersects(child, set)) {
+                Some(child) => current = child,
+                // No child intersects, so the current object is the best match.
+                None => return Some(current),
+            }
+        }
+        Some(current)
+    }
+
+    /// Returns the set of largest objects which exactly cover the `CpuSet`.
+    ///
+    /// The objects are returned in tree order and their CPU sets don't overlap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType, CpuSet};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    ///
+    /// let objects = topology.largest_objects_inside_cpuset(&CpuSet::from_range(0, 5));
+    /// assert_eq!(ObjectType::Package, objects[0].object_type());
+    /// assert_eq!(ObjectType::Core, objects[1].object_type());
+    /// ```
+    pub fn largest_objects_inside_cpuset(&self, set: &CpuSet) -> Vec<&TopologyObject> {
+        let mut remaining = set.clone();
+        let mut objects = Vec::new();
+
+        while !remaining.is_empty() {
+            let object = match self.first_largest_object_inside_cpuset(&remaining) {
+                Some(object) => object,
+                None => break,
+            };
+            match object.cpuset() {
+                Some(cpuset) => remaining -= &*cpuset,
+                None => break,
+            }
+            objects.push(object);
+        }
+        objects
+    }
+
+    /// Returns the next object of the given type whose CPU set intersects the `CpuSet`.
+    ///
+    /// If `prev` is `None`, the first matching object is returned, otherwise the
+    /// search continues after `prev`. This allows iterating over all objects of
+    /// a type which cover a part of `set`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType, CpuSet};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    /// let set = CpuSet::from_range(1, 2);
+    ///
+    /// let first = topology.next_object_covering_cpuset_by_type(&set, &ObjectType::Core, None)
+    ///     .unwrap();
+    /// let second = topology
+    ///     .next_object_covering_cpuset_by_type(&set, &ObjectType::Core, Some(first))
+    ///     .unwrap();
+    /// assert_eq!(0, first.logical_index());
+    /// assert_eq!(1, second.logical_index());
+    /// assert!(topology
+    ///     .next_object_covering_cpuset_by_type(&set, &ObjectType::Core, Some(second))
+    ///     .is_none());
+    /// ```
+    pub fn next_object_covering_cpuset_by_type<'a>(&'a self,
+                                                   set: &CpuSet,
+                                                   object_type: &ObjectType,
+                                                   prev: Option<&'a TopologyObject>)
+                                                   -> Option<&'a TopologyObject> {
+        let depth = match self.depth_for_type(object_type) {
+            Ok(depth) => depth,
+            Err(_) => return None,
+        };
+
+        let mut next = match prev {
+            Some(prev) if prev.depth() != depth => return None,
+            Some(prev) => prev.next_cousin(),
+            None => self.objects_at_depth(depth).into_iter().next(),
+        };
+
+        while let Some(object) = next {
+            if intersects(object, set) {
+                return Some(object);
+            }
+            next = object.next_cousin();
+        }
+        None
+    }
+
+    /// Distributes `n` items (for example threads) over the given root objects.
+    ///
+    /// This is the equivalent of hwloc's `hwloc_distrib` helper. Each root gets a
+    /// share of the items proportional to the number of PUs it contains, and the
+    /// shares are recursively split among its children until a single item is
+    /// left for an object or the depth of `until` is reached. The returned vector
+    /// contains one `CpuSet` per item, ordered like the tree (or in reverse order
+    /// if `DISTRIB_REVERSE` is given).
+    ///
+    /// If `until` does not exist in the topology, the next level below it is
+    /// used. Callers will usually `singlify` the resulting sets before binding.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType, CpuSet, DistribFlags};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    /// let root = topology.object_at_root();
+    ///
+    /// let sets = topology.distribute(&[root], 2, &ObjectType::PU, DistribFlags::empty());
+    /// assert_eq!(vec![CpuSet::from_range(0, 3), CpuSet::from_range(4, 7)], sets);
+    /// ```
+    pub fn distribute(&self,
+                      roots: &[&TopologyObject],
+                      n: usize,
+                      until: &ObjectType,
+                      flags: DistribFlags)
+                      -> Vec<CpuSet> {
+        let until = self.depth_or_below_for_type(until).unwrap_or(u32::MAX);
+        let mut sets = Vec::with_capacity(n);
+        distribute_into(roots, n, until, flags.contains(DISTRIB_REVERSE), &mut sets);
+        sets
+    }
+
+    /// Converts a `CpuSet` into the `NodeSet` of the NUMA nodes near its CPUs.
+    ///
+    /// Every NUMA node whose CPU set intersects `set` is included. If the topology
+    /// contains no NUMA node, all memory is close to every CPU, so the result is full
+    /// unless `set` is empty. This is hwloc's `hwloc_cpuset_to_nodeset`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, CpuSet};
+    ///
+    /// let topology = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+    ///
+    /// assert_eq!("1", topology.cpuset_to_nodeset(&CpuSet::from_range(4, 5)).to_string());
+    /// assert_eq!("0-1", topology.cpuset_to_nodeset(&CpuSet::from_range(3, 4)).to_string());
+    /// ```
+    pub fn cpuset_to_nodeset(&self, set: &CpuSet) -> NodeSet {
+        let nodes = self.objects_with_type(&ObjectType::NUMANode).unwrap_or_default();
+        if nodes.is_empty() {
+            return if set.is_empty() { NodeSet::new() } else { NodeSet::full() };
+        }
+
+        nodes.into_iter()
+            .filter(|node| intersects(node, set))
+            .map(|node| node.os_index())
+            .collect()
+    }
+
+    /// Converts a `NodeSet` into the `CpuSet` of the CPUs near its NUMA nodes.
+    ///
+    /// This is the union of the CPU sets of the NUMA nodes in `set`. If the topology
+    /// contains no NUMA node, the result is full unless `set` is empty. This is hwloc's
+    /// `hwloc_cpuset_from_nodeset`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, NodeSet};
+    ///
+    /// let topology = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+    ///
+    /// assert_eq!("4-7", topology.nodeset_to_cpuset(&NodeSet::from(1)).to_string());
+    /// assert_eq!("", topology.nodeset_to_cpuset(&NodeSet::from(2)).to_string());
+    /// ```
+    pub fn nodeset_to_cpuset(&self, set: &NodeSet) -> CpuSet {
+        let nodes = self.objects_with_type(&ObjectType::NUMANode).unwrap_or_default();
+        if nodes.is_empty() {
+            return if set.is_empty() { CpuSet::new() } else { CpuSet::full() };
+        }
+
+        let mut cpuset = CpuSet::new();
+        for node in nodes.into_iter().filter(|node| set.is_set(node.os_index())) {
+            if let Some(node_cpuset) = node.cpuset() {
+                cpuset |= &*node_cpuset;
+            }
+        }
+        cpuset
+    }
+
+    /// Binds the current process or thread on CPUs given in the `CpuSet`.
+    pub fn set_cpubind(&mut self, set: CpuSet, flags: CpuBindFlags) -> Result<(), CpuBindError> {
+        let result = unsafe { ffi::hwloc_set_cpubind(self.topo, set.as_ptr(), flags.bits()) };
+
+        match result {
+            r if r < 0 => {
+                let e = errno();
+                Err(CpuBindError::Generic(e.0 as i32, format!("{}", e)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get current process or thread binding.
+    pub fn get_cpubind(&self, flags: CpuBindFlags) -> Option<CpuSet> {
+        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
+        let res = unsafe { ffi::hwloc_get_cpubind(self.topo, raw_set, flags.bits()) };
+        if res >= 0 {
+            Some(unsafe { CpuSet::from_raw(raw_set) })
+        } else {
+            None
+        }
+    }
+
+    /// Binds a process (identified by its `pid`) on CPUs identified by the given `CpuSet`.
+    pub fn set_cpubind_for_process(&mut self,
+                                   pid: pid_t,
+                                   set: CpuSet,
+                                   flags: CpuBindFlags)
+                                   -> Result<(), CpuBindError> {
+        let result =
+            unsafe { ffi::hwloc_set_proc_cpubind(self.topo, pid, set.as_ptr(), flags.bits()) };
+
+        match result {
+            r if r < 0 => {
+                let e = errno();
+                Err(CpuBindError::Generic(e.0 as i32, format!("{}", e)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the current physical binding of a process, identified by its `pid`.
+    pub fn get_cpubind_for_process(&self, pid: pid_t, flags: CpuBindFlags) -> Option<CpuSet> {
+        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
+        let res = unsafe { ffi::hwloc_get_proc_cpubind(self.topo, pid, raw_set, flags.bits()) };
+        if res >= 0 {
+            Some(unsafe { CpuSet::from_raw(raw_set) })
+        } else {
+            None
+        }
+    }
+
+    /// Bind a thread (by its `tid`) on CPUs given in through the `CpuSet`.
+    pub fn set_cpubind_for_thread(&mut self,
+                                  tid: pthread_t,
+                                  set: CpuSet,
+                                  flags: CpuBindFlags)
+                                  -> Result<(), CpuBindError> {
+        let result =
+            unsafe { ffi::hwloc_set_thread_cpubind(self.topo, tid, set.as_ptr(), flags.bits()) };
+
+        match result {
+            r if r < 0 => {
+                let e = errno();
+                Err(CpuBindError::Generic(e.0 as i32, format!("{}", e)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the current physical binding of thread `tid`.
+    pub fn get_cpubind_for_thread(&self, tid: pthread_t, flags: CpuBindFlags) -> Option<CpuSet> {
+        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
+        let res = unsafe { ffi::hwloc_get_thread_cpubind(self.topo, tid, raw_set, flags.bits()) };
+        if res >= 0 {
+            Some(unsafe { CpuSet::from_raw(raw_set) })
+        } else {
+            None
+        }
+    }
+
+    /// Get the last physical CPU where the current process or thread ran.
+    ///
+    /// The operating system may move some tasks from one processor
+    /// to another at any time according to their binding,
+    /// so this function may return something that is already
+    /// outdated.
+    ///
+    /// Flags can include either `CPUBIND_PROCESS` or `CPUBIND_THREAD` to
+    /// specify whether the query should be for the whole process (union of all CPUs
+    /// on which all threads are running), or only the current thread. If the
+    /// process is single-threaded, flags can be set to zero to let hwloc use
+    /// whichever method is available on the underlying OS.
+    pub fn get_cpu_location(&self, flags: CpuBindFlags) -> Option<CpuSet> {
+        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
+        let res = unsafe { ffi::hwloc_get_last_cpu_location(self.topo, raw_set, flags.bits()) };
+        if res >= 0 {
+            Some(unsafe { CpuSet::from_raw(raw_set) })
+        } else {
+            None
+        }
+    }
 
     /// Get the last physical CPU where a process ran.
     ///
@@ -542,7 +1125,7 @@ impl Topology {
         let res =
             unsafe { ffi::hwloc_get_proc_last_cpu_location(self.topo, pid, raw_set, flags.bits()) };
         if res >= 0 {
-            Some(CpuSet::from_raw(raw_set, true))
+            Some(unsafe { CpuSet::from_raw(raw_set) })
         } else {
             None
         }
@@ -550,26 +1133,329 @@ impl Topology {
 
     // Added by long 20210707 ---------------------------------------------------------
     pub fn set_membind(&self, set: CpuSet, policy: MemBindPolicy, flags: MemBindFlags) -> Result<(), MemBindError> {
-        let result = unsafe { ffi::hwloc_set_membind(self.topo, set.as_ptr(), policy, flags.bits()) };
+        let result = unsafe {
+            ffi::hwloc_set_membind(self.topo, set.as_ptr(), policy as c_int, flags.bits())
+        };
         match result {
-            r if r < 0 => {
-                let e = errno();
-                Err(MemBindError::Generic(e.0 as i32, format!("{}", e)))
-            }
+            r if r < 0 => Err(MemBindError::from_errno()),
             _ => Ok(()),
         }
     }
 
     pub fn get_membind(&self, policy: &mut MemBindPolicy, flags: MemBindFlags) -> Option<CpuSet> {
-        let raw_set = unsafe { ffi::hwloc_bitmap_alloc() };
-        let res = unsafe { ffi::hwloc_get_membind(self.topo, raw_set, policy, flags.bits()) };
-        if res >= 0 {
-            Some(CpuSet::from_raw(raw_set, true))
-        } else {
-            None
+        let mut set = CpuSet::new();
+        let mut raw_policy = 0;
+        let res = unsafe {
+            ffi::hwloc_get_membind(self.topo, set.as_mut_ptr(), &mut raw_policy, flags.bits())
+        };
+        match MemBindPolicy::from_i32(raw_policy) {
+            Some(p) if res >= 0 => {
+                *policy = p;
+                Some(set)
+            }
+            _ => None,
         }
     }
     // --------------------------------------------------------------------------------
+
+    /// Binds the memory of the current process or thread to the NUMA nodes in `set`,
+    /// following `policy`.
+    ///
+    /// Unlike `set_membind`, this takes a `NodeSet` and thus also works for NUMA nodes
+    /// without CPUs. `MEMBIND_BYNODESET` is added to `flags` automatically.
+    ///
+    /// The request is checked against `TopologyMemBindSupport` first, so it fails with
+    /// `MemBindError::Unsupported` without calling into hwloc if the system cannot bind
+    /// memory this way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, NodeSet, MemBindPolicy, MemBindError, MEMBIND_PROCESS};
+    ///
+    /// let topology = Topology::new();
+    /// let nodeset = topology.object_at_root().nodeset().unwrap().clone();
+    ///
+    /// match topology.set_membind_nodeset(&nodeset, MemBindPolicy::Bind, MEMBIND_PROCESS) {
+    ///     Ok(()) | Err(MemBindError::Unsupported) => (),
+    ///     Err(e) => panic!("binding failed: {:?}", e),
+    /// }
+    /// ```
+    pub fn set_membind_nodeset(&self,
+                               set: &NodeSet,
+                               policy: MemBindPolicy,
+                               flags: MemBindFlags)
+                               -> Result<(), MemBindError> {
+        self.check_membind_support(Some(policy), flags, true)?;
+        let result = unsafe {
+            ffi::hwloc_set_membind(self.topo,
+                                   set.as_ptr(),
+                                   policy as c_int,
+                                   (flags | MEMBIND_BYNODESET).bits())
+        };
+        match result {
+            r if r < 0 => Err(MemBindError::from_errno()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the NUMA nodes and the policy the memory of the current process or thread
+    /// is bound to.
+    ///
+    /// Like `set_membind_nodeset`, the request is checked against
+    /// `TopologyMemBindSupport` first.
+    pub fn get_membind_nodeset(&self,
+                               flags: MemBindFlags)
+                               -> Result<(NodeSet, MemBindPolicy), MemBindError> {
+        self.check_membind_support(None, flags, false)?;
+        let mut set = NodeSet::new();
+        let mut policy = 0;
+        let result = unsafe {
+            ffi::hwloc_get_membind(self.topo,
+                                   set.as_mut_ptr(),
+                                   &mut policy,
+                                   (flags | MEMBIND_BYNODESET).bits())
+        };
+        match result {
+            r if r < 0 => Err(MemBindError::from_errno()),
+            _ => Ok((set, MemBindPolicy::from_raw(policy)?)),
+        }
+    }
+
+    /// Checks that binding the current process or thread with `policy` and `flags` is
+    /// supported, for setting the binding if `set` is true and for getting it otherwise.
+    fn check_membind_support(&self,
+                             policy: Option<MemBindPolicy>,
+                             flags: MemBindFlags,
+                             set: bool)
+                             -> Result<(), MemBindError> {
+        let support = self.support().memory();
+        let target_supported = match (flags.contains(MEMBIND_PROCESS),
+                                      flags.contains(MEMBIND_THREAD),
+                                      set) {
+            (true, _, true) => support.set_current_process(),
+            (_, true, true) => support.set_current_thread(),
+            (_, _, true) => support.set_current_process() || support.set_current_thread(),
+            (true, _, false) => support.get_current_process(),
+            (_, true, false) => support.get_current_thread(),
+            (_, _, false) => support.get_current_process() || support.get_current_thread(),
+        };
+        let policy_supported = match policy {
+            None | Some(MemBindPolicy::Default) => true,
+            Some(MemBindPolicy::FirstTouch) => support.first_touch(),
+            Some(MemBindPolicy::Bind) => support.bind(),
+            Some(MemBindPolicy::Interleave) => support.interleave(),
+            Some(MemBindPolicy::Replicate) => support.replicate(),
+            Some(MemBindPolicy::NextTouch) => support.next_touch(),
+            Some(MemBindPolicy::Mixed) => {
+                return Err(MemBindError::Generic(libc::EINVAL,
+                                                 "MemBindPolicy::Mixed cannot be set".into()))
+            }
+        };
+        let migrate_supported = !flags.contains(MEMBIND_MIGRATE) || support.migrate();
+
+        if target_supported && policy_supported && migrate_supported {
+            Ok(())
+        } else {
+            Err(MemBindError::Unsupported)
+        }
+    }
+
+    /// Sets the memory binding policy of a process, identified by its `pid`, to the
+    /// memory near the CPUs in `set`.
+    ///
+    /// Fails with `MemBindError::Unsupported` if the system cannot bind the memory of
+    /// other processes.
+    pub fn set_membind_for_process(&self,
+                                   pid: pid_t,
+                                   set: CpuSet,
+                                   policy: MemBindPolicy,
+                                   flags: MemBindFlags)
+                                   -> Result<(), MemBindError> {
+        let result = unsafe {
+            ffi::hwloc_set_proc_membind(self.topo, pid, set.as_ptr(), policy as c_int, flags.bits())
+        };
+        match result {
+            r if r < 0 => Err(MemBindError::from_errno()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the CPUs near the memory a process, identified by its `pid`, is bound
+    /// to, together with its binding policy.
+    ///
+    /// If the threads of the process have differing policies, the returned policy is
+    /// `MemBindPolicy::Mixed`. With `MEMBIND_STRICT` in `flags`, differing sets are an error.
+    pub fn get_membind_for_process(&self,
+                                   pid: pid_t,
+                                   flags: MemBindFlags)
+                                   -> Result<(CpuSet, MemBindPolicy), MemBindError> {
+        let mut set = CpuSet::new();
+        let mut policy = 0;
+        let result = unsafe {
+            ffi::hwloc_get_proc_membind(self.topo,
+                                        pid,
+                                        set.as_mut_ptr(),
+                                        &mut policy,
+                                        flags.bits())
+        };
+        match result {
+            r if r < 0 => Err(MemBindError::from_errno()),
+            _ => Ok((set, MemBindPolicy::from_raw(policy)?)),
+        }
+    }
+
+    /// Binds the memory of `area` to the NUMA nodes in `set`, following `policy`.
+    ///
+    /// Only the pages covering `area` are affected, the binding of the rest of the process
+    /// is left untouched. Pass `MEMBIND_MIGRATE` in `flags` to also move pages which are
+    /// already allocated.
+    ///
+    /// Fails with `MemBindError::Unsupported` if the system cannot bind memory areas, or
+    /// with `MemBindError::CannotEnforce` if `MEMBIND_STRICT` is given and the binding
+    /// cannot be enforced.
+    pub fn set_area_membind(&self,
+                            area: &[u8],
+                            set: &NodeSet,
+                            policy: MemBindPolicy,
+                            flags: MemBindFlags)
+                            -> Result<(), MemBindError> {
+        let result = unsafe {
+            ffi::hwloc_set_area_membind(self.topo,
+                                        area.as_ptr() as *const c_void,
+                                        area.len(),
+                                        set.as_ptr(),
+                                        policy as c_int,
+                                        (flags | MEMBIND_BYNODESET).bits())
+        };
+        match result {
+            r if r < 0 => Err(MemBindError::from_errno()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the NUMA nodes and the policy `area` is bound to.
+    ///
+    /// If the pages of `area` have differing policies, the returned policy is
+    /// `MemBindPolicy::Mixed`. With `MEMBIND_STRICT` in `flags`, differing node sets are an
+    /// error, otherwise the union of all node sets is returned.
+    pub fn get_area_membind(&self,
+                            area: &[u8],
+                            flags: MemBindFlags)
+                            -> Result<(NodeSet, MemBindPolicy), MemBindError> {
+        let mut set = NodeSet::new();
+        let mut policy = 0;
+        let result = unsafe {
+            ffi::hwloc_get_area_membind(self.topo,
+                                        area.as_ptr() as *const c_void,
+                                        area.len(),
+                                        set.as_mut_ptr(),
+                                        &mut policy,
+                                        (flags | MEMBIND_BYNODESET).bits())
+        };
+        match result {
+            r if r < 0 => Err(MemBindError::from_errno()),
+            _ => Ok((set, MemBindPolicy::from_raw(policy)?)),
+        }
+    }
+
+    /// Returns the NUMA nodes where the pages of `area` are actually allocated.
+    ///
+    /// Unlike `get_area_membind`, this reports the physical location of the pages, which
+    /// may differ from their binding, for example after first touch. Pages which have not
+    /// been touched yet are not allocated anywhere and thus not reported.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hwloc::{Topology, MemBindFlags};
+    ///
+    /// let topology = Topology::new();
+    /// let buffer = vec![1u8; 1 << 20];
+    ///
+    /// let nodes = topology.get_area_memlocation(&buffer, MemBindFlags::empty()).unwrap();
+    /// println!("buffer lives on NUMA nodes {}", nodes);
+    /// ```
+    pub fn get_area_memlocation(&self,
+                                area: &[u8],
+                                flags: MemBindFlags)
+                                -> Result<NodeSet, MemBindError> {
+        let mut set = NodeSet::new();
+        let result = unsafe {
+            ffi::hwloc_get_area_memlocation(self.topo,
+                                            area.as_ptr() as *const c_void,
+                                            area.len(),
+                                            set.as_mut_ptr(),
+                                            (flags | MEMBIND_BYNODESET).bits())
+        };
+        match result {
+            r if r < 0 => Err(MemBindError::from_errno()),
+            _ => Ok(set),
+        }
+    }
+
+    /// Moves the pages of `area` to the NUMA nodes in `set` and binds them there.
+    ///
+    /// The pages are counted per NUMA node with `get_area_memlocation` before and after the
+    /// migration, which makes this rather slow for large areas. With `strict`, the
+    /// migration fails with `MemBindError::CannotEnforce` if some pages could not be moved,
+    /// otherwise they are left where they are.
+    ///
+    /// hwloc rounds `area` out to whole pages, so any other data sharing the first or last
+    /// page with it, like the rest of a `Vec` it was sliced from, is moved and rebound too.
+    ///
+    /// Fails with `MemBindError::Unsupported` without touching the area if the system
+    /// cannot bind areas or migrate memory, see `TopologyMemBindSupport`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use hwloc::{Topology, NodeSet};
+    ///
+    /// let topology = Topology::new();
+    /// let cache = vec![1u8; 1 << 20];
+    ///
+    /// let report = topology.migrate_area(&cache, &NodeSet::from(1), false).unwrap();
+    /// println!("moved {} of {} pages to node 1", report.pages_gained(1), report.total_pages);
+    /// ```
+    pub fn migrate_area(&self,
+                        area: &[u8],
+                        set: &NodeSet,
+                        strict: bool)
+                        -> Result<MigrationReport, MemBindError> {
+        let support = self.support().memory();
+        if !support.set_area() || !support.migrate() {
+            return Err(MemBindError::Unsupported);
+        }
+
+        let (total_pages, pages_before) = migration::page_counts(self, area)?;
+        let flags = if strict {
+            MEMBIND_MIGRATE | MEMBIND_STRICT
+        } else {
+            MEMBIND_MIGRATE
+        };
+        self.set_area_membind(area, set, MemBindPolicy::Bind, flags)?;
+        let (_, pages_after) = migration::page_counts(self, area)?;
+
+        Ok(MigrationReport { total_pages, pages_before, pages_after })
+    }
+
+    /// Allocates a zero-initialized buffer of `len` bytes bound to the NUMA nodes in `set`.
+    ///
+    /// The memory is returned to hwloc when the `NumaBuffer` is dropped. Without
+    /// `MEMBIND_STRICT` in `flags`, hwloc falls back to an unbound allocation if the
+    /// system cannot bind memory. Use `NumaBox` and `NumaVec` to store typed values.
+    ///
+    /// Fails with `MemBindError::Unsupported` if `MEMBIND_STRICT` is given and the
+    /// system cannot allocate bound memory.
+    pub fn alloc_membind(&self,
+                         len: usize,
+                         set: &NodeSet,
+                         policy: MemBindPolicy,
+                         flags: MemBindFlags)
+                         -> Result<NumaBuffer<'_>, MemBindError> {
+        NumaBuffer::new(self, len, set, policy, flags)
+    }
 }
 
 impl Drop for Topology {
@@ -589,10 +1475,103 @@ pub enum CpuBindError {
     Generic(i32, String),
 }
 
+/// Divides `a` by `b`, rounding up.
+fn div_round_up(a: usize, b: usize) -> usize {
+    if a == 0 { 0 } else { (a - 1) / b + 1 }
+}
+
+/// Checks if the CPU set of `object` includes all of `set`.
+fn covers(object: &TopologyObject, set: &CpuSet) -> bool {
+    matches!(object.cpuset(), Some(cpuset) if set.is_included_in(&cpuset))
+}
+
+/// Checks if the CPU set of `object` has at least one index in common with `set`.
+fn intersects(object: &TopologyObject, set: &CpuSet) -> bool {
+    matches!(object.cpuset(), Some(cpuset) if cpuset.intersects(set))
+}
+
+/// Recursive part of `Topology::distribute`, appending one `CpuSet` per item to `sets`.
+fn distribute_into(roots: &[&TopologyObject],
+                   n: usize,
+                   until: u32,
+                   reverse: bool,
+                   sets: &mut Vec<CpuSet>) {
+    let weight_of = |object: &TopologyObject| {
+        object.cpuset().map_or(0, |cpuset| cpuset.weight().max(0) as usize)
+    };
+
+    let total_weight = roots.iter().map(|root| weight_of(root)).sum::<usize>();
+    if n == 0 || total_weight == 0 {
+        return;
+    }
+
+    let mut given_weight = 0;
+    for i in 0..roots.len() {
+        let root = roots[if reverse { roots.len() - 1 - i } else { i }];
+        let weight = weight_of(root);
+        if weight == 0 {
+            continue;
+        }
+
+        // Give the root a chunk proportional to its weight. If previous chunks
+        // got rounded up, this one may be a bit smaller.
+        let chunk = div_round_up((given_weight + weight) * n, total_weight) -
+                    div_round_up(given_weight * n, total_weight);
+
+        if root.arity() == 0 || chunk <= 1 || root.depth() >= until {
+            let cpuset = root.cpuset().unwrap();
+            if chunk > 0 {
+                for _ in 0..chunk {
+                    sets.push(cpuset.clone());
+                }
+            } else if let Some(last) = sets.last_mut() {
+                // Nothing left for this root, merge it into the previous chunk so
+                // that its PUs are not ignored.
+                *last |= &*cpuset;
+            }
+        } else {
+            distribute_into(&root.children(), chunk, until, reverse, sets);
+        }
+        given_weight += weight;
+    }
+}
+
+/// Errors which can occur while creating, loading or exporting a `Topology`.
+#[derive(Debug)]
+pub enum TopologyError {
+    /// hwloc could not allocate and initialize the topology context.
+    Init(i32, String),
+    /// hwloc rejected the requested topology configuration.
+    Config(i32, String),
+    /// Loading (discovering) the topology failed.
+    Load(i32, String),
+    /// Exporting the topology failed.
+    Export(i32, String),
+    /// A path or string argument could not be passed to hwloc, for example
+    /// because it contains an interior nul byte.
+    InvalidArgument(String),
+}
+
 // Added by long 20210707
 #[derive(Debug)]
 pub enum MemBindError {
     Generic(i32, String),
+    /// The requested memory binding is not supported by the system (`ENOSYS`).
+    Unsupported,
+    /// The binding cannot be enforced, for example with `MEMBIND_STRICT` (`EXDEV`).
+    CannotEnforce,
+}
+
+impl MemBindError {
+    /// Builds the error for a failed memory binding call from the current `errno`.
+    pub(crate) fn from_errno() -> MemBindError {
+        let e = errno();
+        match e.0 {
+            libc::ENOSYS => MemBindError::Unsupported,
+            libc::EXDEV => MemBindError::CannotEnforce,
+            code => MemBindError::Generic(code, format!("{}", e)),
+        }
+    }
 }
 
 bitflags! {
@@ -624,39 +1603,82 @@ bitflags! {
     }
 }
 
+/// The policy of a memory binding, which decides where memory is allocated.
+///
+/// **Note:** Not all systems support all policies, see `TopologyMemBindSupport`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MemBindPolicy {
+    /// Reset the memory allocation policy to the system default. Depending on the operating
+    /// system, this may correspond to `FirstTouch` (Linux), or `Bind` (AIX, HP-UX, OSF,
+    /// Solaris, Windows).
+    Default = 0,
+    /// Allocate memory but do not immediately bind it to a specific locality. Instead,
+    /// each page in the allocation is bound only when it is first touched. Pages are
+    /// individually bound to the local NUMA node of the first thread that touches it. If
+    /// there is not enough memory on the node, allocation may be done in the specified
+    /// cpuset before allocating on other nodes.
+    FirstTouch = 1,
+    /// Allocate memory on the specified nodes.
+    Bind = 2,
+    /// Allocate memory on the given nodes in an interleaved / round-robin manner.
+    /// The precise layout of the memory across multiple NUMA nodes is OS/system specific.
+    /// Interleaving can be useful when threads distributed across the specified NUMA nodes
+    /// will all be accessing the whole memory range concurrently, since the interleave will
+    /// then balance the memory references.
+    Interleave = 3,
+    /// Replicate memory on the given nodes; reads from this memory will attempt to be
+    /// serviced from the NUMA node local to the reading thread. Replicating can be useful
+    /// when multiple threads from the specified NUMA nodes will be sharing the same read-only
+    /// data.
+    Replicate = 4,
+    /// For each page bound with this policy, by next time it is touched (and next time
+    /// only), it is moved from its current location to the local NUMA node of the thread
+    /// where the memory reference occurred (if it needs to be moved at all).
+    NextTouch = 5,
+    /// Returned by the `get_membind` functions when multiple threads or parts of a memory
+    /// area have differing memory binding policies. It cannot be set.
+    Mixed = -1,
+}
+
+impl MemBindPolicy {
+    /// Converts a policy returned by hwloc, failing if it is unknown.
+    fn from_raw(policy: c_int) -> Result<MemBindPolicy, MemBindError> {
+        MemBindPolicy::from_i32(policy).ok_or_else(|| {
+            MemBindError::Generic(libc::EINVAL, format!("unknown memory binding policy {}", policy))
+        })
+    }
+}
+
+impl FromPrimitive for MemBindPolicy {
+    fn from_i64(n: i64) -> Option<Self> {
+        match n {
+            0 => Some(MemBindPolicy::Default),
+            1 => Some(MemBindPolicy::FirstTouch),
+            2 => Some(MemBindPolicy::Bind),
+            3 => Some(MemBindPolicy::Interleave),
+            4 => Some(MemBindPolicy::Replicate),
+            5 => Some(MemBindPolicy::NextTouch),
+            -1 => Some(MemBindPolicy::Mixed),
+            _ => None,
+        }
+    }
+
+    fn from_u64(n: u64) -> Option<Self> {
+        FromPrimitive::from_i64(n as i64)
+    }
+}
+
 bitflags! {
-    #[repr(transparent)]
-    pub flags MemBindPolicy: i32 {
-        /// Reset the memory allocation policy to the system default. Depending on the operating
-        /// system, this may correspond to MEMBIND_FIRSTTOUCH (Linux), or MEMBIND_BIND (AIX,
-        /// HP-UX, OSF, Solaris, Windows).
-        const MEMBIND_DEFAULT = 0,
-        /// Allocate memory but do not immediately bind it to a specific locality. Instead,
-        /// each page in the allocation is bound only when it is first touched. Pages are
-        /// individually bound to the local NUMA node of the first thread that touches it. If
-        /// there is not enough memory on the node, allocation may be done in the specified
-        /// cpuset before allocating on other nodes.
-        const MEMBIND_FIRSTTOUCH = 1,
-        /// Allocate memory on the specified nodes.
-        const MEMBIND_BIND = 2,
-        /// Allocate memory on the given nodes in an interleaved / round-robin manner.
-        /// The precise layout of the memory across multiple NUMA nodes is OS/system specific.
-        /// Interleaving can be useful when threads distributed across the specified NUMA nodes
-        /// will all be accessing the whole memory range concurrently, since the interleave will
-        /// then balance the memory references.
-        const MEMBIND_INTERLEAVE = 3,
-        /// Replicate memory on the given nodes; reads from this memory will attempt to be
-        /// serviced from the NUMA node local to the reading thread. Replicating can be useful
-        /// when multiple threads from the specified NUMA nodes will be sharing the same read-only
-        /// data.
-        const MEMBIND_REPLICATE = 4,
-        /// For each page bound with this policy, by next time it is touched (and next time
-        /// only), it is moved from its current location to the local NUMA node of the thread
-        /// where the memory reference occurred (if it needs to be moved at all).
-        const MEMBIND_NEXTTOUCH = 5,
-        /// Returned by get_membind() functions when multiple threads or parts of a memory
-        /// area have differing memory binding policies.
-        const MEMBIND_MIXED = -1,
+    /// Flags to tweak how `Topology::distribute` spreads items over the topology.
+    ///
+    /// The following flags (constants) are available:
+    ///
+    /// - **DISTRIB_REVERSE:** Distribute in reverse order, starting from the last objects.
+    pub flags DistribFlags: u32 {
+        /// Distribute in reverse order, starting from the last objects.
+        const DISTRIB_REVERSE = (1<<0),
     }
 }
 
@@ -729,23 +1751,465 @@ mod tests {
 
     #[test]
     fn should_get_topology_depth() {
-        let topo = Topology::new();
-        assert!(topo.depth() > 0);
+        let topo = Topology::from_synthetic("node:2 pack:2 core:2 pu:2").unwrap();
+        assert_eq!(5, topo.depth());
     }
 
     #[test]
     fn should_match_types_and_their_depth() {
+        let topo = Topology::from_synthetic("node:2 pack:2 core:2 pu:2").unwrap();
+
+        assert_eq!(Ok(0), topo.depth_for_type(&ObjectType::Machine));
+        assert_eq!(Ok(1), topo.depth_for_type(&ObjectType::NUMANode));
+        assert_eq!(Ok(2), topo.depth_for_type(&ObjectType::Package));
+        assert_eq!(Ok(3), topo.depth_for_type(&ObjectType::Core));
+        assert_eq!(Ok(4), topo.depth_for_type(&ObjectType::PU));
+        assert_eq!(ObjectType::PU, topo.type_at_depth(4));
+        assert_eq!(Err(TypeDepthError::TypeDepthUnknown),
+                   topo.depth_for_type(&ObjectType::Group));
+    }
+
+    #[test]
+    fn should_get_nbobjs_by_depth() {
+        let topo = Topology::from_synthetic("node:2 pack:2 core:2 pu:2").unwrap();
+        assert_eq!(1, topo.size_at_depth(0));
+        assert_eq!(2, topo.size_at_depth(1));
+        assert_eq!(16, topo.size_at_depth(4));
+    }
+
+    #[test]
+    fn should_get_objects_with_type() {
+        let topo = Topology::from_synthetic("node:2 pack:2 core:2 pu:2").unwrap();
+
+        assert_eq!(4, topo.objects_with_type(&ObjectType::Package).unwrap().len());
+        assert_eq!(8, topo.objects_with_type(&ObjectType::Core).unwrap().len());
+        assert!(topo.objects_with_type(&ObjectType::Group).is_err());
+    }
+
+    #[test]
+    fn should_roundtrip_synthetic_description() {
+        let topo = Topology::from_synthetic("pack:2 core:3 pu:2").unwrap();
+        let description = topo.export_synthetic().unwrap();
+
+        let rebuilt = Topology::from_synthetic(&description).unwrap();
+        assert_eq!(topo.depth(), rebuilt.depth());
+        for depth in 0..topo.depth() {
+            assert_eq!(topo.size_at_depth(depth), rebuilt.size_at_depth(depth));
+        }
+    }
+
+    #[test]
+    fn should_find_object_covering_cpuset() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+
+        let core = topo.object_covering_cpuset(&CpuSet::from_range(2, 3)).unwrap();
+        assert_eq!(ObjectType::Core, core.object_type());
+        assert_eq!(1, core.logical_index());
+
+        let pu = topo.object_covering_cpuset(&CpuSet::from(5)).unwrap();
+        assert_eq!(ObjectType::PU, pu.object_type());
+        assert_eq!(5, pu.os_index());
+
+        let mut set = CpuSet::from(0);
+        set.set(4);
+        let machine = topo.object_covering_cpuset(&set).unwrap();
+        assert_eq!(ObjectType::Machine, machine.object_type());
+
+        assert!(topo.object_covering_cpuset(&CpuSet::new()).is_none());
+        assert!(topo.object_covering_cpuset(&CpuSet::from(100)).is_none());
+    }
+
+    #[test]
+    fn should_find_objects_inside_cpuset_by_type() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let set = CpuSet::from_range(0, 4);
+
+        assert_eq!(1, topo.objects_inside_cpuset_by_type(&set, &ObjectType::Package).len());
+        assert_eq!(2, topo.objects_inside_cpuset_by_type(&set, &ObjectType::Core).len());
+        assert_eq!(5, topo.objects_inside_cpuset_by_type(&set, &ObjectType::PU).len());
+        assert!(topo.objects_inside_cpuset_by_type(&set, &ObjectType::Group).is_empty());
+    }
+
+    #[test]
+    fn should_find_largest_objects_inside_cpuset() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let set = CpuSet::from_range(0, 6);
+
+        let first = topo.first_largest_object_inside_cpuset(&set).unwrap();
+        assert_eq!(ObjectType::Package, first.object_type());
+        assert_eq!(0, first.logical_index());
+
+        let largest = topo.largest_objects_inside_cpuset(&set)
+            .iter()
+            .map(|o| (o.object_type(), o.logical_index()))
+            .collect::<Vec<(ObjectType, u32)>>();
+        assert_eq!(vec![(ObjectType::Package, 0), (ObjectType::Core, 2), (ObjectType::PU, 6)],
+                   largest);
+
+        assert!(topo.first_largest_object_inside_cpuset(&CpuSet::new()).is_none());
+        assert!(topo.largest_objects_inside_cpuset(&CpuSet::new()).is_empty());
+    }
+
+    #[test]
+    fn should_iterate_objects_covering_cpuset_by_type() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let mut set = CpuSet::from(1);
+        set.set(6);
+
+        let mut found = Vec::new();
+        let mut prev = None;
+        while let Some(core) = topo.next_object_covering_cpuset_by_type(&set,
+                                                                         &ObjectType::Core,
+                                                                         prev) {
+            found.push(core.logical_index());
+            prev = Some(core);
+        }
+        assert_eq!(vec![0, 3], found);
+
+        let pu = topo.objects_with_type(&ObjectType::PU).unwrap()[0];
+        assert!(topo.next_object_covering_cpuset_by_type(&set, &ObjectType::Core, Some(pu))
+            .is_none());
+    }
+
+    #[test]
+    fn should_convert_between_cpuset_and_nodeset() {
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+
+        assert_eq!(NodeSet::from(0), topo.cpuset_to_nodeset(&CpuSet::from_range(0, 3)));
+        assert_eq!(NodeSet::from_range(0, 1), topo.cpuset_to_nodeset(&CpuSet::full()));
+        assert!(topo.cpuset_to_nodeset(&CpuSet::new()).is_empty());
+
+        assert_eq!(CpuSet::from_range(0, 3), topo.nodeset_to_cpuset(&NodeSet::from(0)));
+        assert_eq!(CpuSet::from_range(0, 7), topo.nodeset_to_cpuset(&NodeSet::full()));
+    }
+
+    #[test]
+    fn should_convert_without_numa_nodes() {
+        let topo = Topology::from_synthetic("core:2 pu:2").unwrap();
+
+        assert!(topo.cpuset_to_nodeset(&CpuSet::from(1)).is_full());
+        assert!(topo.cpuset_to_nodeset(&CpuSet::new()).is_empty());
+        assert!(topo.nodeset_to_cpuset(&NodeSet::from(0)).is_full());
+    }
+
+    /// Returns the first whole page of `buffer`, which has to span at least two pages.
+    fn first_page(buffer: &[u8]) -> &[u8] {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let offset = (page_size - buffer.as_ptr() as usize % page_size) % page_size;
+        &buffer[offset..offset + page_size]
+    }
+
+    #[test]
+    fn should_bind_and_locate_area() {
         let topo = Topology::new();
+        let buffer = vec![1u8; 1 << 16];
+        let area = first_page(&buffer);
+        let nodeset = topo.object_at_root().nodeset().unwrap().clone();
 
-        let pu_depth = topo.depth_for_type(&ObjectType::PU).ok().unwrap();
-        assert!(pu_depth > 0);
-        assert_eq!(ObjectType::PU, topo.type_at_depth(pu_depth));
+        match topo.set_area_membind(area, &nodeset, MemBindPolicy::Bind, MemBindFlags::empty()) {
+            Ok(()) => (),
+            Err(e) => assert!(matches!(e, MemBindError::Unsupported), "{:?}", e),
+        }
+        match topo.get_area_membind(area, MemBindFlags::empty()) {
+            Ok((set, _)) => assert!(!set.is_empty() && set.is_included_in(&nodeset)),
+            Err(e) => assert!(matches!(e, MemBindError::Unsupported), "{:?}", e),
+        }
+        match topo.get_area_memlocation(area, MemBindFlags::empty()) {
+            Ok(location) => assert!(location.is_included_in(&nodeset)),
+            Err(e) => assert!(matches!(e, MemBindError::Unsupported), "{:?}", e),
+        }
     }
 
     #[test]
-    fn should_get_nbobjs_by_depth() {
+    fn should_not_bind_area_without_support() {
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+        let buffer = vec![1u8; 1 << 16];
+        let area = first_page(&buffer);
+        let nodeset = NodeSet::from(1);
+
+        assert!(matches!(topo.set_area_membind(area, &nodeset, MemBindPolicy::Bind,
+                                               MemBindFlags::empty()),
+                         Err(MemBindError::Unsupported)));
+        assert!(matches!(topo.get_area_membind(area, MemBindFlags::empty()),
+                         Err(MemBindError::Unsupported)));
+        assert!(matches!(topo.get_area_memlocation(area, MemBindFlags::empty()),
+                         Err(MemBindError::Unsupported)));
+    }
+
+    #[test]
+    fn should_get_membind_of_own_process() {
         let topo = Topology::new();
-        assert!(topo.size_at_depth(1) > 0);
+        let pid = unsafe { libc::getpid() };
+
+        match topo.get_membind_for_process(pid, MemBindFlags::empty()) {
+            Ok((set, _)) => assert!(!set.is_empty()),
+            Err(e) => assert!(matches!(e, MemBindError::Unsupported), "{:?}", e),
+        }
+    }
+
+    #[test]
+    fn should_map_membind_errno() {
+        errno::set_errno(errno::Errno(libc::ENOSYS));
+        assert!(matches!(MemBindError::from_errno(), MemBindError::Unsupported));
+
+        errno::set_errno(errno::Errno(libc::EXDEV));
+        assert!(matches!(MemBindError::from_errno(), MemBindError::CannotEnforce));
+
+        errno::set_errno(errno::Errno(libc::EINVAL));
+        assert!(matches!(MemBindError::from_errno(),
+                         MemBindError::Generic(code, _) if code == libc::EINVAL));
+    }
+
+    #[test]
+    fn should_not_migrate_without_support() {
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+        let area = vec![0u8; 8192];
+
+        assert!(matches!(topo.migrate_area(&area, &NodeSet::from(1), true),
+                         Err(MemBindError::Unsupported)));
+    }
+
+    #[test]
+    fn should_migrate_area_to_own_node() {
+        let topo = Topology::new();
+        let area = vec![1u8; 1 << 16];
+        let nodeset = topo.object_at_root().nodeset().unwrap().clone();
+
+        match topo.migrate_area(&area, &nodeset, false) {
+            Ok(report) => {
+                assert!(report.total_pages > 0);
+                assert!(report.pages_after.values().sum::<usize>() <= report.total_pages);
+                assert!(report.pages_after.keys().all(|&node| nodeset.is_set(node)));
+            }
+            Err(e) => assert!(matches!(e, MemBindError::Unsupported), "{:?}", e),
+        }
+    }
+
+    #[test]
+    fn should_convert_membind_policy() {
+        for &policy in &[MemBindPolicy::Default,
+                         MemBindPolicy::FirstTouch,
+                         MemBindPolicy::Bind,
+                         MemBindPolicy::Interleave,
+                         MemBindPolicy::Replicate,
+                         MemBindPolicy::NextTouch,
+                         MemBindPolicy::Mixed] {
+            assert_eq!(Some(policy), MemBindPolicy::from_i32(policy as i32));
+        }
+        assert_eq!(None, MemBindPolicy::from_i32(6));
+        assert!(MemBindPolicy::from_raw(-2).is_err());
+    }
+
+    #[test]
+    fn should_validate_membind_nodeset() {
+        // Synthetic topologies don't support binding at all.
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+        let nodeset = NodeSet::from(0);
+
+        assert!(matches!(topo.set_membind_nodeset(&nodeset, MemBindPolicy::Bind, MEMBIND_PROCESS),
+                         Err(MemBindError::Unsupported)));
+        assert!(matches!(topo.get_membind_nodeset(MEMBIND_THREAD),
+                         Err(MemBindError::Unsupported)));
+        assert!(matches!(topo.set_membind_nodeset(&nodeset, MemBindPolicy::Mixed, MEMBIND_PROCESS),
+                         Err(MemBindError::Generic(code, _)) if code == libc::EINVAL));
+    }
+
+    #[test]
+    fn should_send_snapshot_to_other_thread() {
+        fn assert_send<T: Send>() {}
+        assert_send::<ObjectSnapshot>();
+
+        let topo = Topology::from_synthetic("node:2 core:2 pu:2").unwrap();
+        let snapshot = topo.snapshot();
+        let cpuset = std::thread::spawn(move || snapshot.children[1].cpuset.clone())
+            .join()
+            .unwrap();
+        assert_eq!("4-7", cpuset.unwrap().to_string());
+    }
+
+    #[test]
+    fn should_get_cache_attributes_only_for_caches() {
+        let topo = Topology::from_synthetic("pack:1 l2:2 core:1 pu:1").unwrap();
+
+        let caches = topo.objects_with_type(&ObjectType::Cache).unwrap();
+        assert_eq!(2, caches.len());
+        for cache in caches {
+            let attributes = cache.cache_attributes().unwrap();
+            assert_eq!(2, attributes.depth());
+            assert_eq!(CacheType::Unified, attributes.cache_type());
+        }
+
+        assert!(topo.object_at_root().cache_attributes().is_none());
+        let pus = topo.objects_with_type(&ObjectType::PU).unwrap();
+        assert!(pus.iter().all(|pu| pu.cache_attributes().is_none()));
+    }
+
+    #[test]
+    fn should_find_ancestors() {
+        let topo = Topology::from_synthetic("node:2 pack:1 core:2 pu:2").unwrap();
+        let pus = topo.objects_with_type(&ObjectType::PU).unwrap();
+
+        let node = pus[5].ancestor_of_type(&ObjectType::NUMANode).unwrap();
+        assert_eq!(1, node.logical_index());
+        assert!(pus[5].ancestor_of_type(&ObjectType::PU).is_none());
+        assert!(topo.object_at_root().ancestor_of_type(&ObjectType::Machine).is_none());
+
+        let core = pus[5].ancestor_at_depth(3).unwrap();
+        assert_eq!(ObjectType::Core, core.object_type());
+        assert_eq!(2, core.logical_index());
+        assert_eq!(ObjectType::Machine, pus[5].ancestor_at_depth(0).unwrap().object_type());
+        assert!(pus[5].ancestor_at_depth(4).is_none());
+        assert!(core.ancestor_at_depth(4).is_none());
+    }
+
+    #[test]
+    fn should_find_common_ancestor() {
+        let topo = Topology::from_synthetic("node:2 pack:1 core:2 pu:2").unwrap();
+        let pus = topo.objects_with_type(&ObjectType::PU).unwrap();
+
+        let ancestor = |a: usize, b: usize| {
+            let object = pus[a].common_ancestor(pus[b]).unwrap();
+            (object.object_type(), object.logical_index())
+        };
+        assert_eq!((ObjectType::PU, 3), ancestor(3, 3));
+        assert_eq!((ObjectType::Core, 1), ancestor(2, 3));
+        assert_eq!((ObjectType::Package, 0), ancestor(0, 3));
+        assert_eq!((ObjectType::Machine, 0), ancestor(1, 6));
+
+        let core = pus[2].parent().unwrap();
+        assert_eq!(1, pus[2].common_ancestor(core).unwrap().logical_index());
+        assert_eq!(1, core.common_ancestor(pus[3]).unwrap().logical_index());
+    }
+
+    #[test]
+    fn should_check_subtree_membership() {
+        let topo = Topology::from_synthetic("node:2 pack:1 core:2 pu:2").unwrap();
+        let nodes = topo.objects_with_type(&ObjectType::NUMANode).unwrap();
+        let pus = topo.objects_with_type(&ObjectType::PU).unwrap();
+
+        assert!(pus[0].is_in_subtree(nodes[0]));
+        assert!(pus[7].is_in_subtree(nodes[1]));
+        assert!(!pus[7].is_in_subtree(nodes[0]));
+        assert!(nodes[0].is_in_subtree(nodes[0]));
+        assert!(nodes[0].is_in_subtree(topo.object_at_root()));
+    }
+
+    #[test]
+    fn should_get_object_by_type_and_index() {
+        let topo = Topology::from_synthetic("node:2 pack:2 core:2 pu:2").unwrap();
+
+        let package = topo.object_by_type_and_index(&ObjectType::Package, 3).unwrap();
+        assert_eq!(ObjectType::Package, package.object_type());
+        assert_eq!(3, package.logical_index());
+
+        assert!(topo.object_by_type_and_index(&ObjectType::Package, 4).is_none());
+        assert!(topo.object_by_type_and_index(&ObjectType::Group, 0).is_none());
+        assert!(topo.object_by_type_and_index(&ObjectType::PCIDevice, 0).is_none());
+    }
+
+    #[test]
+    fn should_get_objects_by_os_index() {
+        let topo = Topology::from_synthetic("node:2 pack:2 core:2 pu:2").unwrap();
+
+        let pu = topo.pu_by_os_index(13).unwrap();
+        assert_eq!(ObjectType::PU, pu.object_type());
+        assert_eq!(13, pu.os_index());
+        assert!(topo.pu_by_os_index(16).is_none());
+
+        let node = topo.numa_node_by_os_index(1).unwrap();
+        assert_eq!(ObjectType::NUMANode, node.object_type());
+        assert_eq!(1, node.os_index());
+        assert!(topo.numa_node_by_os_index(2).is_none());
+    }
+
+    #[test]
+    fn should_distribute_over_synthetic_topology() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let root = topo.object_at_root();
+        let distribute = |n: usize, until: ObjectType, flags: DistribFlags| {
+            topo.distribute(&[root], n, &until, flags)
+                .iter()
+                .map(|set| format!("{}", set))
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(vec!["0-7"], distribute(1, ObjectType::PU, DistribFlags::empty()));
+        assert_eq!(vec!["0-3", "4-7"], distribute(2, ObjectType::PU, DistribFlags::empty()));
+        assert_eq!(vec!["0-1", "2-3", "4-7"],
+                   distribute(3, ObjectType::PU, DistribFlags::empty()));
+        assert_eq!(vec!["0-1", "2-3", "4-5", "6-7"],
+                   distribute(4, ObjectType::PU, DistribFlags::empty()));
+        assert_eq!(vec!["0", "1", "2", "3", "4", "5", "6", "7"],
+                   distribute(8, ObjectType::PU, DistribFlags::empty()));
+        assert_eq!(16, distribute(16, ObjectType::PU, DistribFlags::empty()).len());
+        assert!(distribute(0, ObjectType::PU, DistribFlags::empty()).is_empty());
+    }
+
+    #[test]
+    fn should_distribute_until_type() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let root = topo.object_at_root();
+
+        let sets = topo.distribute(&[root], 4, &ObjectType::Package, DistribFlags::empty());
+        assert_eq!(vec![CpuSet::from_range(0, 3),
+                        CpuSet::from_range(0, 3),
+                        CpuSet::from_range(4, 7),
+                        CpuSet::from_range(4, 7)],
+                   sets);
+    }
+
+    #[test]
+    fn should_distribute_in_reverse() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let root = topo.object_at_root();
+
+        let sets = topo.distribute(&[root], 3, &ObjectType::PU, DISTRIB_REVERSE);
+        assert_eq!(vec![CpuSet::from_range(6, 7),
+                        CpuSet::from_range(4, 5),
+                        CpuSet::from_range(0, 3)],
+                   sets);
+    }
+
+    #[test]
+    fn should_distribute_over_multiple_roots() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let packages = topo.objects_with_type(&ObjectType::Package).unwrap();
+
+        let sets = topo.distribute(&packages, 2, &ObjectType::PU, DistribFlags::empty());
+        assert_eq!(vec![CpuSet::from_range(0, 3), CpuSet::from_range(4, 7)], sets);
+    }
+
+    #[test]
+    fn should_borrow_object_sets_from_topology() {
+        let topo = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+        let root = topo.object_at_root();
+
+        let cpuset = root.cpuset().unwrap();
+        assert_eq!(CpuSet::from_range(0, 7), cpuset);
+
+        let mut owned = cpuset.clone();
+        owned.singlify();
+        owned.set(42);
+        assert_eq!(2, owned.weight());
+
+        // Modifying the owned copy leaves the topology untouched.
+        assert_eq!(8, cpuset.weight());
+        assert_eq!(root.cpuset().unwrap(), cpuset);
+        assert_eq!(vec![0, 1, 2, 3, 4, 5, 6, 7], cpuset.iter().collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn should_check_if_topology_is_this_system() {
+        assert!(Topology::new().is_this_system());
+        assert!(!Topology::from_synthetic("pack:2 pu:2").unwrap().is_this_system());
+
+        let xml = Topology::new().export_xml_buffer().unwrap();
+        assert!(!Topology::from_xml_buffer(&xml).unwrap().is_this_system());
+    }
+
+    #[test]
+    fn should_fail_on_invalid_synthetic_description() {
+        assert!(Topology::from_synthetic("this is not valid").is_err());
     }
 
     #[test]
@@ -788,6 +2252,34 @@ mod tests {
         assert_eq!(false, topo.support().cpu().set_current_thread());
     }
 
+    #[test]
+    fn should_roundtrip_xml_buffer() {
+        let topo = Topology::new();
+        let xml = topo.export_xml_buffer().unwrap();
+
+        let imported = Topology::from_xml_buffer(&xml).unwrap();
+        assert_eq!(topo.depth(), imported.depth());
+        assert_eq!(topo.size_at_depth(topo.depth() - 1),
+                   imported.size_at_depth(imported.depth() - 1));
+    }
+
+    #[test]
+    fn should_roundtrip_xml_file() {
+        let topo = Topology::new();
+        let path = std::env::temp_dir().join("hwloc-rs-should-roundtrip-xml-file.xml");
+        topo.export_xml(&path).unwrap();
+
+        let imported = Topology::from_xml_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(topo.depth(), imported.depth());
+    }
+
+    #[test]
+    fn should_fail_to_load_invalid_xml() {
+        assert!(Topology::from_xml_buffer("this is not a topology").is_err());
+        assert!(Topology::from_xml_file("/this/path/does/not/exist.xml").is_err());
+    }
+
     #[test]
     fn should_produce_cpubind_bitflags() {
         assert_eq!("1", format!("{:b}", CPUBIND_PROCESS.bits()));
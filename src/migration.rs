@@ -0,0 +1,60 @@
+use std::cmp;
+use std::collections::BTreeMap;
+
+use libc;
+
+use {Topology, MemBindError, MemBindFlags};
+
+/// The outcome of `Topology::migrate_area`.
+///
+/// Page counts are keyed by the OS index of the NUMA node holding the pages. Pages which
+/// have not been touched yet are not allocated on any node and thus not counted, so the
+/// counts may add up to less than `total_pages`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MigrationReport {
+    /// The number of pages spanned by the migrated area.
+    pub total_pages: usize,
+    /// The number of pages on each NUMA node before the migration.
+    pub pages_before: BTreeMap<u32, usize>,
+    /// The number of pages on each NUMA node after the migration.
+    pub pages_after: BTreeMap<u32, usize>,
+}
+
+impl MigrationReport {
+    /// Returns the number of pages that were on `node` after the migration but not before.
+    pub fn pages_gained(&self, node: u32) -> usize {
+        let before = self.pages_before.get(&node).cloned().unwrap_or(0);
+        let after = self.pages_after.get(&node).cloned().unwrap_or(0);
+        after.saturating_sub(before)
+    }
+}
+
+/// Counts the pages of `area` on each NUMA node, returning the total number of pages
+/// spanned by the area and the count per node OS index.
+pub fn page_counts(topology: &Topology,
+                   area: &[u8])
+                   -> Result<(usize, BTreeMap<u32, usize>), MemBindError> {
+    let page_size = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size <= 0 => {
+            return Err(MemBindError::Generic(libc::EINVAL, "cannot determine the page size".into()))
+        }
+        size => size as usize,
+    };
+    let start = area.as_ptr() as usize;
+    let mut total = 0;
+    let mut counts = BTreeMap::new();
+
+    let mut offset = 0;
+    while offset < area.len() {
+        let end = cmp::min(area.len(), offset + page_size - (start + offset) % page_size);
+        let location = topology.get_area_memlocation(&area[offset..end], MemBindFlags::empty())?;
+        let node = location.first();
+        if node >= 0 {
+            *counts.entry(node as u32).or_insert(0) += 1;
+        }
+        total += 1;
+        offset = end;
+    }
+    Ok((total, counts))
+}
@@ -0,0 +1,898 @@
+use ffi;
+use div_round_up;
+use bitmap::{Bitmap, ParseBitmapError};
+use libc::{c_uint, c_ulong};
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use std::ops::{Not, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Sub,
+               SubAssign};
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+
+const WORD_BITS: usize = 64;
+
+/// Width of the `unsigned long` hwloc stores bitmaps in, which drives its hex formats.
+const ULONG_BITS: usize = mem::size_of::<c_ulong>() * 8;
+
+/// A bitmap implemented in Rust, with the same semantics as the hwloc `Bitmap`.
+///
+/// Every operation on a `Bitmap` is a call into hwloc, which adds up when sets are combined
+/// in tight loops. A `PureBitmap` keeps its bits in Rust memory instead and only talks to
+/// hwloc when converted from or into a `Bitmap`, which is what hwloc functions such as
+/// `Topology::set_cpubind` take.
+///
+/// Like a `Bitmap` it may be of infinite size, and it is formatted and parsed exactly as
+/// hwloc does.
+///
+/// Examples:
+///
+/// ```
+/// use hwloc::{Bitmap, PureBitmap};
+///
+/// let mut bitmap = PureBitmap::from_range(0, 3);
+/// bitmap.set_range(8, -1);
+/// bitmap = bitmap - &PureBitmap::from_range(10, 11);
+/// assert_eq!("0-3,8-9,12-", bitmap.to_string());
+///
+/// let hwloc_bitmap: Bitmap = (&bitmap).into();
+/// assert_eq!("0-3,8-9,12-", hwloc_bitmap.to_string());
+/// assert_eq!(bitmap, PureBitmap::from(&hwloc_bitmap));
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct PureBitmap {
+    /// The finite part of the bitmap, trailing words equal to the tail are never stored.
+    words: Vec<u64>,
+    /// Whether all indexes beyond `words` are set.
+    infinite: bool,
+}
+
+impl PureBitmap {
+    /// Creates an empty `PureBitmap`.
+    pub fn new() -> PureBitmap {
+        PureBitmap { words: Vec::new(), infinite: false }
+    }
+
+    /// Creates a full `PureBitmap`.
+    pub fn full() -> PureBitmap {
+        PureBitmap { words: Vec::new(), infinite: true }
+    }
+
+    /// Creates a new `PureBitmap` with the given range.
+    ///
+    /// If `end` is -1 the range is infinite, just like `Bitmap::from_range`.
+    pub fn from_range(begin: u32, end: i32) -> PureBitmap {
+        let mut bitmap = PureBitmap::new();
+        bitmap.set_range(begin, end);
+        bitmap
+    }
+
+    /// Parses a list string like `0-3,8,12-` as printed by `to_list_string`.
+    ///
+    /// # Failures
+    ///
+    /// Fails with a `ParseBitmapError` if `s` is not a comma-separated list of decimal
+    /// indexes and ranges, where only the last range may be open.
+    pub fn from_list_str(s: &str) -> Result<PureBitmap, ParseBitmapError> {
+        fn index(s: &str, input: &str) -> Result<u32, ParseBitmapError> {
+            s.trim().parse::<u32>().map_err(|_| ParseBitmapError::new(input))
+        }
+
+        let mut bitmap = PureBitmap::new();
+        let mut tokens = s.split(',').map(str::trim).filter(|t| !t.is_empty()).peekable();
+        while let Some(token) = tokens.next() {
+            match token.find('-') {
+                None => bitmap.set(index(token, s)?),
+                Some(dash) => {
+                    let begin = index(&token[..dash], s)?;
+                    let end = &token[dash + 1..];
+                    if end.trim().is_empty() {
+                        if tokens.peek().is_some() {
+                            return Err(ParseBitmapError::new(s));
+                        }
+                        bitmap.set_range(begin, -1);
+                    } else {
+                        let end = index(end, s)?;
+                        if end > i32::MAX as u32 {
+                            return Err(ParseBitmapError::new(s));
+                        }
+                        bitmap.set_range(begin, end as i32);
+                    }
+                }
+            }
+        }
+        Ok(bitmap)
+    }
+
+    /// Formats the bitmap as a list of ranges, like `Bitmap::to_list_string`.
+    pub fn to_list_string(&self) -> String {
+        let mut out = String::new();
+        for (begin, end) in self.ranges() {
+            if !out.is_empty() {
+                out.push(',');
+            }
+            if end == -1 {
+                out.push_str(&format!("{}-", begin));
+            } else if end as u32 == begin {
+                out.push_str(&format!("{}", begin));
+            } else {
+                out.push_str(&format!("{}-{}", begin, end));
+            }
+        }
+        out
+    }
+
+    /// Formats the bitmap as comma-separated 32 bit hex masks, like `Bitmap::to_hex_string`.
+    pub fn to_hex_string(&self) -> String {
+        let mut out = String::new();
+        let mut needcomma = false;
+        if self.infinite {
+            out.push_str("0xf...f");
+            needcomma = true;
+        }
+
+        let mut i = self.skip_leading_ulongs(0);
+        let mut accum = 0;
+        let mut accumed = 0;
+        while i >= 0 || accumed > 0 {
+            if accumed == 0 {
+                accum = self.ulong(i as usize);
+                i -= 1;
+                accumed = ULONG_BITS;
+            }
+
+            let chunk = (accum >> (ULONG_BITS - 32)) & 0xffff_ffff;
+            let comma = if needcomma { "," } else { "" };
+            if chunk != 0 {
+                out.push_str(&format!("{}0x{:08x}", comma, chunk));
+                needcomma = true;
+            } else if i == -1 && accumed == 32 {
+                out.push_str(&format!("{}0x0", comma));
+            } else if needcomma {
+                out.push(',');
+            }
+
+            accum = if accumed > 32 { accum << 32 } else { 0 };
+            accumed -= 32;
+        }
+
+        if out.is_empty() {
+            out.push_str("0x0");
+        }
+        out
+    }
+
+    /// Formats the bitmap as a single hex mask, like `Bitmap::to_taskset_string`.
+    pub fn to_taskset_string(&self) -> String {
+        let mut out = String::new();
+        let mut started = false;
+        if self.infinite {
+            out.push_str("0xf...f");
+            started = true;
+        }
+
+        let mut i = self.skip_leading_ulongs(1);
+        while i >= 0 {
+            let val = self.ulong(i as usize);
+            i -= 1;
+            if started {
+                out.push_str(&format!("{:01$x}", val, ULONG_BITS / 4));
+            } else if val != 0 || i == -1 {
+                out.push_str(&format!("0x{:x}", val));
+                started = true;
+            }
+        }
+
+        if out.is_empty() {
+            out.push_str("0x0");
+        }
+        out
+    }
+
+    /// Set index `id` in this `PureBitmap`.
+    pub fn set(&mut self, id: u32) {
+        let (word, bit) = position(id);
+        self.resize(word + 1);
+        self.words[word] |= bit;
+        self.normalize();
+    }
+
+    /// Adds indexes from `begin` to `end` in the bitmap, `end` -1 meaning infinite.
+    ///
+    /// Nothing is added if `end` is smaller than `begin`.
+    pub fn set_range(&mut self, begin: u32, end: i32) {
+        if end == -1 {
+            let (word, _) = position(begin);
+            self.resize(word + 1);
+            self.words.truncate(word + 1);
+            self.words[word] |= !0 << (begin as usize % WORD_BITS);
+            self.infinite = true;
+        } else if end >= 0 && end as u32 >= begin {
+            self.resize(end as usize / WORD_BITS + 1);
+            for (word, mask) in masks(begin, end as u32) {
+                self.words[word] |= mask;
+            }
+        }
+        self.normalize();
+    }
+
+    /// Remove index `id` from the `PureBitmap`.
+    pub fn unset(&mut self, id: u32) {
+        let (word, bit) = position(id);
+        self.resize(word + 1);
+        self.words[word] &= !bit;
+        self.normalize();
+    }
+
+    /// Remove indexes from `begin` to `end` in the bitmap, `end` -1 meaning infinite.
+    ///
+    /// Nothing is removed if `end` is smaller than `begin`.
+    pub fn unset_range(&mut self, begin: u32, end: i32) {
+        if end == -1 {
+            let (word, _) = position(begin);
+            self.resize(word + 1);
+            self.words.truncate(word + 1);
+            self.words[word] &= !(!0 << (begin as usize % WORD_BITS));
+            self.infinite = false;
+        } else if end >= 0 && end as u32 >= begin {
+            self.resize(end as usize / WORD_BITS + 1);
+            for (word, mask) in masks(begin, end as u32) {
+                self.words[word] &= !mask;
+            }
+        }
+        self.normalize();
+    }
+
+    /// Keep a single index among those set in the bitmap, the first one.
+    pub fn singlify(&mut self) {
+        let first = self.first();
+        self.clear();
+        if first >= 0 {
+            self.set(first as u32);
+        }
+    }
+
+    /// Clears the `PureBitmap`.
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.infinite = false;
+    }
+
+    /// Checks if this `PureBitmap` has indexes set.
+    pub fn is_empty(&self) -> bool {
+        !self.infinite && self.words.is_empty()
+    }
+
+    /// Check if the field with the given id is set.
+    pub fn is_set(&self, id: u32) -> bool {
+        let (word, bit) = position(id);
+        self.word(word) & bit != 0
+    }
+
+    /// Returns the number of indexes that are in the bitmap, -1 if infinite.
+    pub fn weight(&self) -> i32 {
+        if self.infinite {
+            -1
+        } else {
+            self.words.iter().map(|w| w.count_ones() as i32).sum()
+        }
+    }
+
+    /// Inverts the current `PureBitmap`.
+    pub fn invert(&mut self) {
+        for word in &mut self.words {
+            *word = !*word;
+        }
+        self.infinite = !self.infinite;
+    }
+
+    /// Compute the first index (least significant bit) in this `PureBitmap`.
+    ///
+    /// Returns -1 if no index is set.
+    pub fn first(&self) -> i32 {
+        match self.next_set(0) {
+            Some(id) => id as i32,
+            None => -1,
+        }
+    }
+
+    /// Compute the last index (most significant bit) in this `PureBitmap`.
+    ///
+    /// Returns -1 if no index is set or if the bitmap is infinite.
+    pub fn last(&self) -> i32 {
+        if self.infinite {
+            return -1;
+        }
+        match self.words.last() {
+            Some(word) => {
+                ((self.words.len() - 1) * WORD_BITS + WORD_BITS - 1 -
+                 word.leading_zeros() as usize) as i32
+            }
+            None => -1,
+        }
+    }
+
+    /// Test whether this `PureBitmap` is completely full.
+    pub fn is_full(&self) -> bool {
+        self.infinite && self.words.is_empty()
+    }
+
+    /// Test whether this `PureBitmap` and `other` have any index in common.
+    pub fn intersects(&self, other: &PureBitmap) -> bool {
+        (self.infinite && other.infinite) ||
+        (0..self.len_with(other)).any(|i| self.word(i) & other.word(i) != 0)
+    }
+
+    /// Test whether every index set in this `PureBitmap` is also set in `other`.
+    pub fn is_included_in(&self, other: &PureBitmap) -> bool {
+        (!self.infinite || other.infinite) &&
+        (0..self.len_with(other)).all(|i| self.word(i) & !other.word(i) == 0)
+    }
+
+    /// Returns a new `PureBitmap` with the indexes set in both this one and `other`.
+    pub fn and(&self, other: &PureBitmap) -> PureBitmap {
+        self.combine(other, |a, b| a & b, |a, b| a && b)
+    }
+
+    /// Returns a new `PureBitmap` with the indexes set in either this one or `other`.
+    pub fn or(&self, other: &PureBitmap) -> PureBitmap {
+        self.combine(other, |a, b| a | b, |a, b| a || b)
+    }
+
+    /// Returns a new `PureBitmap` with the indexes set in exactly one of this one and `other`.
+    pub fn xor(&self, other: &PureBitmap) -> PureBitmap {
+        self.combine(other, |a, b| a ^ b, |a, b| a != b)
+    }
+
+    /// Returns a new `PureBitmap` with the indexes of this one which are not set in `other`.
+    pub fn andnot(&self, other: &PureBitmap) -> PureBitmap {
+        self.combine(other, |a, b| a & !b, |a, b| a && !b)
+    }
+
+    /// Compares this `PureBitmap` and `other` by their first index, like
+    /// `Bitmap::compare_first`.
+    ///
+    /// An empty bitmap is considered higher than any other.
+    pub fn compare_first(&self, other: &PureBitmap) -> Ordering {
+        match (self.first(), other.first()) {
+            (-1, -1) => Ordering::Equal,
+            (-1, _) => Ordering::Greater,
+            (_, -1) => Ordering::Less,
+            (a, b) => a.cmp(&b),
+        }
+    }
+
+    /// Iterates over the set indexes in increasing order.
+    ///
+    /// The iterator never ends for an infinite bitmap.
+    pub fn iter(&self) -> PureBitmapIter<'_> {
+        PureBitmapIter { bitmap: self, next: Some(0) }
+    }
+
+    /// Iterates over the ranges of consecutive set indexes as `(begin, end)` pairs, with
+    /// `end` -1 for the infinite tail, like `Bitmap::ranges`.
+    pub fn ranges(&self) -> PureBitmapRanges<'_> {
+        PureBitmapRanges { bitmap: self, next: Some(0) }
+    }
+
+    /// Returns the first set index at or after `from`.
+    fn next_set(&self, from: u64) -> Option<u32> {
+        self.next_matching(from, false)
+    }
+
+    /// Returns the first unset index at or after `from`.
+    fn next_unset(&self, from: u64) -> Option<u32> {
+        self.next_matching(from, true)
+    }
+
+    fn next_matching(&self, from: u64, unset: bool) -> Option<u32> {
+        let flip = if unset { !0 } else { 0 };
+        let mut word = from as usize / WORD_BITS;
+        let mut bits = (self.word(word) ^ flip) & (!0 << (from as usize % WORD_BITS));
+        loop {
+            if bits != 0 {
+                let id = word * WORD_BITS + bits.trailing_zeros() as usize;
+                return if id <= u32::MAX as usize { Some(id as u32) } else { None };
+            }
+            word += 1;
+            if word >= self.words.len() {
+                let tail = self.infinite != unset;
+                let id = word * WORD_BITS;
+                return if tail && id <= u32::MAX as usize { Some(id as u32) } else { None };
+            }
+            bits = self.words[word] ^ flip;
+        }
+    }
+
+    fn combine<W, T>(&self, other: &PureBitmap, word: W, tail: T) -> PureBitmap
+        where W: Fn(u64, u64) -> u64,
+              T: Fn(bool, bool) -> bool
+    {
+        let mut result = PureBitmap {
+            words: (0..self.len_with(other)).map(|i| word(self.word(i), other.word(i))).collect(),
+            infinite: tail(self.infinite, other.infinite),
+        };
+        result.normalize();
+        result
+    }
+
+    fn len_with(&self, other: &PureBitmap) -> usize {
+        self.words.len().max(other.words.len())
+    }
+
+    /// Returns the word at `index`, including those of the implicit tail.
+    fn word(&self, index: usize) -> u64 {
+        match self.words.get(index) {
+            Some(word) => *word,
+            None => self.tail(),
+        }
+    }
+
+    fn tail(&self) -> u64 {
+        if self.infinite { !0 } else { 0 }
+    }
+
+    /// Stores at least `len` words, filling new ones with the tail.
+    fn resize(&mut self, len: usize) {
+        if self.words.len() < len {
+            let tail = self.tail();
+            self.words.resize(len, tail);
+        }
+    }
+
+    /// Drops trailing words equal to the tail, so equal sets compare and hash equal.
+    fn normalize(&mut self) {
+        let tail = self.tail();
+        while self.words.last() == Some(&tail) {
+            self.words.pop();
+        }
+    }
+
+    /// Number of hwloc `unsigned long`s needed to store the finite part.
+    fn ulongs(&self) -> usize {
+        self.words.len() * (WORD_BITS / ULONG_BITS)
+    }
+
+    /// Returns the `index`th hwloc `unsigned long` of the bitmap.
+    fn ulong(&self, index: usize) -> u64 {
+        let bit = index * ULONG_BITS;
+        let word = self.word(bit / WORD_BITS) >> (bit % WORD_BITS);
+        if ULONG_BITS == WORD_BITS { word } else { word & ((1 << ULONG_BITS) - 1) }
+    }
+
+    /// Index of the first `unsigned long` hwloc prints, it skips the leading ones which
+    /// are the same as the tail but keeps at least `keep` of them for finite bitmaps.
+    fn skip_leading_ulongs(&self, keep: isize) -> isize {
+        let full = if ULONG_BITS == WORD_BITS { !0 } else { (1 << ULONG_BITS) - 1 };
+        let (skip, keep) = if self.infinite { (full, 0) } else { (0, keep) };
+        let mut i = self.ulongs() as isize - 1;
+        while i >= keep && self.ulong(i as usize) == skip {
+            i -= 1;
+        }
+        i
+    }
+}
+
+/// Returns the word holding `id` and the mask of `id` in it.
+fn position(id: u32) -> (usize, u64) {
+    (id as usize / WORD_BITS, 1 << (id as usize % WORD_BITS))
+}
+
+/// Returns the words and masks covering the indexes from `begin` to `end` inclusive.
+fn masks(begin: u32, end: u32) -> Vec<(usize, u64)> {
+    let (first, last) = (begin as usize / WORD_BITS, end as usize / WORD_BITS);
+    (first..last + 1)
+        .map(|word| {
+            let mut mask = !0u64;
+            if word == first {
+                mask &= !0 << (begin as usize % WORD_BITS);
+            }
+            if word == last {
+                mask &= !0 >> (WORD_BITS - 1 - end as usize % WORD_BITS);
+            }
+            (word, mask)
+        })
+        .collect()
+}
+
+impl<'a> From<&'a Bitmap> for PureBitmap {
+    fn from(bitmap: &'a Bitmap) -> PureBitmap {
+        // Only the bits up to the infinite tail, or up to the last one, need to be copied.
+        let infinite = bitmap.weight() < 0;
+        let end = if infinite { (!bitmap.clone()).last() + 1 } else { bitmap.last() + 1 } as usize;
+
+        let ulongs = div_round_up(end, ULONG_BITS);
+        let mut words = vec![0; div_round_up(ulongs, WORD_BITS / ULONG_BITS)];
+        for i in 0..ulongs {
+            let ulong = unsafe { ffi::hwloc_bitmap_to_ith_ulong(bitmap.as_ptr(), i as c_uint) };
+            let bit = i * ULONG_BITS;
+            words[bit / WORD_BITS] |= (ulong as u64) << (bit % WORD_BITS);
+        }
+
+        let mut result = PureBitmap { words, infinite };
+        if infinite {
+            // Bits of the last word beyond the hwloc ulongs belong to the tail.
+            let bits = ulongs * ULONG_BITS;
+            let offset = bits % WORD_BITS;
+            if offset > 0 {
+                result.words[bits / WORD_BITS] |= !0 << offset;
+            }
+        }
+        result.normalize();
+        result
+    }
+}
+
+impl From<Bitmap> for PureBitmap {
+    fn from(bitmap: Bitmap) -> PureBitmap {
+        PureBitmap::from(&bitmap)
+    }
+}
+
+impl<'a> From<&'a PureBitmap> for Bitmap {
+    fn from(bitmap: &'a PureBitmap) -> Bitmap {
+        let mut result = Bitmap::new();
+        for i in 0..bitmap.ulongs() {
+            unsafe {
+                ffi::hwloc_bitmap_set_ith_ulong(result.as_mut_ptr(),
+                                                i as c_uint,
+                                                bitmap.ulong(i) as c_ulong)
+            }
+        }
+        if bitmap.infinite {
+            result.set_range((bitmap.words.len() * WORD_BITS) as u32, -1);
+        }
+        result
+    }
+}
+
+impl From<PureBitmap> for Bitmap {
+    fn from(bitmap: PureBitmap) -> Bitmap {
+        (&bitmap).into()
+    }
+}
+
+impl Not for PureBitmap {
+    type Output = PureBitmap;
+
+    fn not(mut self) -> PureBitmap {
+        self.invert();
+        self
+    }
+}
+
+impl Not for &PureBitmap {
+    type Output = PureBitmap;
+
+    fn not(self) -> PureBitmap {
+        !self.clone()
+    }
+}
+
+macro_rules! impl_pure_bitmap_op {
+    ($op:ident, $method:ident, $assign_op:ident, $assign_method:ident, $set_op:ident) => {
+        impl<'a, 'b> $op<&'b PureBitmap> for &'a PureBitmap {
+            type Output = PureBitmap;
+
+            fn $method(self, rhs: &'b PureBitmap) -> PureBitmap {
+                self.$set_op(rhs)
+            }
+        }
+
+        impl<'b> $op<&'b PureBitmap> for PureBitmap {
+            type Output = PureBitmap;
+
+            fn $method(self, rhs: &'b PureBitmap) -> PureBitmap {
+                self.$set_op(rhs)
+            }
+        }
+
+        impl<'a> $op<PureBitmap> for &'a PureBitmap {
+            type Output = PureBitmap;
+
+            fn $method(self, rhs: PureBitmap) -> PureBitmap {
+                self.$set_op(&rhs)
+            }
+        }
+
+        impl $op<PureBitmap> for PureBitmap {
+            type Output = PureBitmap;
+
+            fn $method(self, rhs: PureBitmap) -> PureBitmap {
+                self.$set_op(&rhs)
+            }
+        }
+
+        impl<'b> $assign_op<&'b PureBitmap> for PureBitmap {
+            fn $assign_method(&mut self, rhs: &'b PureBitmap) {
+                *self = self.$set_op(rhs)
+            }
+        }
+
+        impl $assign_op<PureBitmap> for PureBitmap {
+            fn $assign_method(&mut self, rhs: PureBitmap) {
+                *self = self.$set_op(&rhs)
+            }
+        }
+    }
+}
+
+impl_pure_bitmap_op!(BitAnd, bitand, BitAndAssign, bitand_assign, and);
+impl_pure_bitmap_op!(BitOr, bitor, BitOrAssign, bitor_assign, or);
+impl_pure_bitmap_op!(BitXor, bitxor, BitXorAssign, bitxor_assign, xor);
+impl_pure_bitmap_op!(Sub, sub, SubAssign, sub_assign, andnot);
+
+impl fmt::Display for PureBitmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_list_string())
+    }
+}
+
+impl fmt::Debug for PureBitmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_list_string())
+    }
+}
+
+impl FromStr for PureBitmap {
+    type Err = ParseBitmapError;
+
+    fn from_str(s: &str) -> Result<PureBitmap, ParseBitmapError> {
+        PureBitmap::from_list_str(s)
+    }
+}
+
+impl FromIterator<u32> for PureBitmap {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> PureBitmap {
+        let mut bitmap = PureBitmap::new();
+        for id in iter {
+            bitmap.set(id);
+        }
+        bitmap
+    }
+}
+
+impl<'a> IntoIterator for &'a PureBitmap {
+    type Item = u32;
+    type IntoIter = PureBitmapIter<'a>;
+
+    fn into_iter(self) -> PureBitmapIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the set indexes of a `PureBitmap`, see `PureBitmap::iter`.
+pub struct PureBitmapIter<'a> {
+    bitmap: &'a PureBitmap,
+    next: Option<u64>,
+}
+
+impl<'a> Iterator for PureBitmapIter<'a> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let id = self.bitmap.next_set(self.next?);
+        self.next = id.map(|id| id as u64 + 1);
+        id
+    }
+}
+
+/// Iterator over the ranges of a `PureBitmap`, see `PureBitmap::ranges`.
+pub struct PureBitmapRanges<'a> {
+    bitmap: &'a PureBitmap,
+    next: Option<u64>,
+}
+
+impl<'a> Iterator for PureBitmapRanges<'a> {
+    type Item = (u32, i32);
+
+    fn next(&mut self) -> Option<(u32, i32)> {
+        let begin = self.bitmap.next_set(self.next?)?;
+        match self.bitmap.next_unset(begin as u64) {
+            Some(end) => {
+                self.next = Some(end as u64);
+                Some((begin, end as i32 - 1))
+            }
+            None => {
+                self.next = None;
+                Some((begin, -1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// Small xorshift generator, so the parity tests are reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u32 {
+            (self.next() % n) as u32
+        }
+    }
+
+    /// Builds the same random set as a `PureBitmap` and a hwloc `Bitmap`.
+    fn random_pair(rng: &mut Rng) -> (PureBitmap, Bitmap) {
+        let mut pure = PureBitmap::new();
+        let mut hwloc = Bitmap::new();
+        for _ in 0..rng.below(8) {
+            let begin = rng.below(300);
+            let end = match rng.below(6) {
+                0 => -1,
+                _ => (begin + rng.below(100)) as i32,
+            };
+            match rng.below(4) {
+                0 => {
+                    pure.set(begin);
+                    hwloc.set(begin);
+                }
+                1 => {
+                    pure.unset_range(begin, end);
+                    hwloc.unset_range(begin, end);
+                }
+                2 if end == -1 => {
+                    pure.invert();
+                    hwloc.invert();
+                }
+                _ => {
+                    pure.set_range(begin, end);
+                    hwloc.set_range(begin, end);
+                }
+            }
+        }
+        (pure, hwloc)
+    }
+
+    fn assert_parity(pure: &PureBitmap, hwloc: &Bitmap) {
+        assert_eq!(hwloc.to_list_string(), pure.to_list_string());
+        assert_eq!(hwloc.to_hex_string(), pure.to_hex_string());
+        assert_eq!(hwloc.to_taskset_string(), pure.to_taskset_string());
+        assert_eq!(hwloc.weight(), pure.weight());
+        assert_eq!(hwloc.first(), pure.first());
+        assert_eq!(hwloc.last(), pure.last());
+        assert_eq!(hwloc.is_empty(), pure.is_empty());
+        assert_eq!(hwloc.is_full(), pure.is_full());
+        assert_eq!(hwloc.ranges().collect::<Vec<_>>(), pure.ranges().collect::<Vec<_>>());
+        assert_eq!(hwloc.iter().take(50).collect::<Vec<_>>(),
+                   pure.iter().take(50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_match_hwloc_for_random_bitmaps() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..500 {
+            let (pure, hwloc) = random_pair(&mut rng);
+            assert_parity(&pure, &hwloc);
+
+            let id = rng.below(400);
+            assert_eq!(hwloc.is_set(id), pure.is_set(id));
+
+            let mut single = pure.clone();
+            let mut hwloc_single = hwloc.clone();
+            single.singlify();
+            hwloc_single.singlify();
+            assert_parity(&single, &hwloc_single);
+        }
+    }
+
+    #[test]
+    fn should_match_hwloc_set_operations() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..300 {
+            let (a, hwloc_a) = random_pair(&mut rng);
+            let (b, hwloc_b) = random_pair(&mut rng);
+
+            assert_parity(&(&a & &b), &(&hwloc_a & &hwloc_b));
+            assert_parity(&(&a | &b), &(&hwloc_a | &hwloc_b));
+            assert_parity(&(&a ^ &b), &(&hwloc_a ^ &hwloc_b));
+            assert_parity(&(&a - &b), &(&hwloc_a - &hwloc_b));
+            assert_parity(&!&a, &!hwloc_a.clone());
+            assert_eq!(hwloc_a.intersects(&hwloc_b), a.intersects(&b));
+            assert_eq!(hwloc_a.is_included_in(&hwloc_b), a.is_included_in(&b));
+            assert_eq!(hwloc_a == hwloc_b, a == b);
+            if !a.is_empty() && !b.is_empty() {
+                assert_eq!(hwloc_a.compare_first(&hwloc_b), a.compare_first(&b));
+            }
+        }
+    }
+
+    #[test]
+    fn should_convert_to_and_from_hwloc() {
+        let mut rng = Rng(0xdead_beef_cafe_f00d);
+        for _ in 0..300 {
+            let (pure, hwloc) = random_pair(&mut rng);
+            assert_eq!(pure, PureBitmap::from(&hwloc));
+            let converted: Bitmap = (&pure).into();
+            assert!(hwloc == converted);
+        }
+    }
+
+    #[test]
+    fn should_parse_what_hwloc_formats() {
+        let mut rng = Rng(0x0123_4567_89ab_cdef);
+        for _ in 0..300 {
+            let (pure, hwloc) = random_pair(&mut rng);
+            let list = hwloc.to_list_string();
+            assert_eq!(pure, list.parse::<PureBitmap>().unwrap());
+            assert!(hwloc == Bitmap::from_list_str(&list).unwrap());
+        }
+    }
+
+    #[test]
+    fn should_format_like_hwloc() {
+        let mut bitmap = PureBitmap::from_range(0, 3);
+        bitmap.set(36);
+        assert_eq!("0-3,36", bitmap.to_list_string());
+        assert_eq!("0x00000010,0x0000000f", bitmap.to_hex_string());
+        assert_eq!("0x100000000f", bitmap.to_taskset_string());
+
+        bitmap.set(100);
+        assert_eq!("0x00000010,,0x00000010,0x0000000f", bitmap.to_hex_string());
+
+        assert_eq!("", PureBitmap::new().to_list_string());
+        assert_eq!("0x0", PureBitmap::new().to_hex_string());
+        assert_eq!("0x0", PureBitmap::new().to_taskset_string());
+        assert_eq!("0x00000001,0x0", PureBitmap::from_range(32, 32).to_hex_string());
+
+        assert_eq!("0-", PureBitmap::full().to_list_string());
+        assert_eq!("0xf...f", PureBitmap::full().to_hex_string());
+        assert_eq!("0xf...f", PureBitmap::full().to_taskset_string());
+
+        let mut bitmap = PureBitmap::full();
+        bitmap.unset(0);
+        assert_eq!("1-", bitmap.to_list_string());
+        assert_eq!("0xf...f,0xffffffff,0xfffffffe", bitmap.to_hex_string());
+        assert_eq!("0xf...ffffffffffffffffe", bitmap.to_taskset_string());
+    }
+
+    #[test]
+    fn should_set_and_unset_ranges() {
+        let mut bitmap = PureBitmap::from_range(60, 130);
+        assert_eq!(71, bitmap.weight());
+        bitmap.unset_range(64, 127);
+        assert_eq!("60-63,128-130", bitmap.to_string());
+
+        bitmap.set_range(200, -1);
+        assert_eq!(-1, bitmap.weight());
+        assert_eq!(-1, bitmap.last());
+        bitmap.unset_range(130, -1);
+        assert_eq!("60-63,128-129", bitmap.to_string());
+        assert_eq!(129, bitmap.last());
+
+        bitmap.set_range(10, 5);
+        assert_eq!("60-63,128-129", bitmap.to_string());
+    }
+
+    #[test]
+    fn should_compare_normalized_bitmaps() {
+        let mut bitmap = PureBitmap::from_range(0, 200);
+        bitmap.unset_range(100, 200);
+        assert_eq!(PureBitmap::from_range(0, 99), bitmap);
+
+        let mut full = PureBitmap::from_range(0, 63);
+        full.set_range(64, -1);
+        assert!(full.is_full());
+        assert_eq!(PureBitmap::full(), full);
+    }
+
+    #[test]
+    fn should_reject_invalid_list_strings() {
+        assert!("1-,4".parse::<PureBitmap>().is_err());
+        assert!("a".parse::<PureBitmap>().is_err());
+        assert!("1-b".parse::<PureBitmap>().is_err());
+        assert_eq!(PureBitmap::from_range(2, -1), "2-".parse::<PureBitmap>().unwrap());
+        assert_eq!(PureBitmap::new(), "".parse::<PureBitmap>().unwrap());
+    }
+
+}
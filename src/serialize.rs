@@ -0,0 +1,137 @@
+//! `Serialize` and `Deserialize` implementations for the types which cannot derive them.
+//!
+//! Bitmaps are written in the list format of `Bitmap::to_list_string`, like `"0-3,8,12-"`,
+//! and flags as their raw bits.
+
+use std::fmt;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de::{self, Visitor};
+
+use bitmap::{Bitmap, BitmapRef};
+use pure_bitmap::PureBitmap;
+use CpuBindFlags;
+
+impl Serialize for Bitmap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_list_string())
+    }
+}
+
+impl<'a, T: Serialize> Serialize for BitmapRef<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl Serialize for PureBitmap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_list_string())
+    }
+}
+
+struct ListStringVisitor;
+
+impl<'de> Visitor<'de> for ListStringVisitor {
+    type Value = PureBitmap;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a bitmap list string like \"0-3,8,12-\"")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<PureBitmap, E> {
+        PureBitmap::from_list_str(s).map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for PureBitmap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<PureBitmap, D::Error> {
+        deserializer.deserialize_str(ListStringVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bitmap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bitmap, D::Error> {
+        // Parsing in Rust keeps hwloc's lenient list parser from accepting malformed input.
+        PureBitmap::deserialize(deserializer).map(Into::into)
+    }
+}
+
+macro_rules! impl_flags_serde {
+    ($flags:ident, $bits:ident) => {
+        impl Serialize for $flags {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.bits().serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $flags {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$flags, D::Error> {
+                let bits = $bits::deserialize(deserializer)?;
+                $flags::from_bits(bits).ok_or_else(|| {
+                    de::Error::custom(format!("invalid {} bits: {}", stringify!($flags), bits))
+                })
+            }
+        }
+    }
+}
+
+impl_flags_serde!(CpuBindFlags, i32);
+
+#[cfg(test)]
+mod tests {
+
+    extern crate serde_json;
+
+    use super::*;
+    use {Topology, ObjectType, TopologyFlag, ObjectSnapshot, CPUBIND_PROCESS, CPUBIND_STRICT,
+         MemBindPolicy};
+
+    #[test]
+    fn should_serialize_bitmap_as_list_string() {
+        let mut bitmap = Bitmap::from_range(0, 3);
+        bitmap.set_range(8, -1);
+
+        let json = serde_json::to_string(&bitmap).unwrap();
+        assert_eq!("\"0-3,8-\"", json);
+
+        let parsed: Bitmap = serde_json::from_str(&json).unwrap();
+        assert_eq!(bitmap, parsed);
+        assert_eq!(PureBitmap::from(&bitmap),
+                   serde_json::from_str::<PureBitmap>(&json).unwrap());
+        assert!(serde_json::from_str::<Bitmap>("\"0-3,x\"").is_err());
+    }
+
+    #[test]
+    fn should_serialize_enums_and_flags() {
+        let json = serde_json::to_string(&ObjectType::Package).unwrap();
+        assert_eq!("\"Package\"", json);
+        assert_eq!(ObjectType::Package, serde_json::from_str(&json).unwrap());
+
+        let json = serde_json::to_string(&TopologyFlag::IoDevices).unwrap();
+        assert_eq!(TopologyFlag::IoDevices,
+                   serde_json::from_str::<TopologyFlag>(&json).unwrap());
+
+        let flags = CPUBIND_PROCESS | CPUBIND_STRICT;
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!("5", json);
+        assert_eq!(flags, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<CpuBindFlags>("64").is_err());
+
+        let json = serde_json::to_string(&MemBindPolicy::Interleave).unwrap();
+        assert_eq!("\"Interleave\"", json);
+        assert_eq!(MemBindPolicy::Interleave, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn should_round_trip_topology_snapshot() {
+        let topology = Topology::from_synthetic("node:2 pack:1 core:2 pu:2").unwrap();
+        let snapshot = topology.snapshot();
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let parsed: ObjectSnapshot = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(snapshot, parsed);
+        assert_eq!(ObjectType::NUMANode, parsed.children[0].object_type);
+        assert_eq!("4-7", parsed.children[1].cpuset.as_ref().unwrap().to_string());
+    }
+}
@@ -0,0 +1,116 @@
+use ffi::ObjectType;
+use bitmap::{Bitmap, BitmapRef};
+use pure_bitmap::PureBitmap;
+use topology_object::{TopologyObject, Associativity, CacheType};
+
+/// An owned copy of a `TopologyObject` and its children.
+///
+/// Unlike a `TopologyObject`, a snapshot does not borrow from the `Topology` it was taken
+/// from, so it can be kept around, sent to other threads or, with the `serde` feature,
+/// serialized and shipped to another process. Its CPU and NUMA node sets are stored as
+/// `PureBitmap`s, which unlike hwloc bitmaps can be sent to other threads.
+///
+/// Examples:
+///
+/// ```
+/// use hwloc::{Topology, ObjectType};
+///
+/// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+/// let snapshot = topology.snapshot();
+///
+/// assert_eq!(ObjectType::Machine, snapshot.object_type);
+/// assert_eq!(2, snapshot.children.len());
+/// assert_eq!("0-7", snapshot.cpuset.unwrap().to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ObjectSnapshot {
+    /// The type of the object.
+    pub object_type: ObjectType,
+    /// The name of the object, if set.
+    pub name: Option<String>,
+    /// The OS-provided physical index number.
+    pub os_index: u32,
+    /// Vertical index in the hierarchy.
+    pub depth: u32,
+    /// Horizontal index in the whole list of similar objects.
+    pub logical_index: u32,
+    /// The local memory (in bytes) in this object.
+    pub local_memory: u64,
+    /// The total memory (in bytes) in this object and its children.
+    pub total_memory: u64,
+    /// The CPUs covered by this object, see `TopologyObject::cpuset`.
+    pub cpuset: Option<PureBitmap>,
+    /// See `TopologyObject::complete_cpuset`.
+    pub complete_cpuset: Option<PureBitmap>,
+    /// See `TopologyObject::online_cpuset`.
+    pub online_cpuset: Option<PureBitmap>,
+    /// See `TopologyObject::allowed_cpuset`.
+    pub allowed_cpuset: Option<PureBitmap>,
+    /// The NUMA nodes covered by this object, see `TopologyObject::nodeset`.
+    pub nodeset: Option<PureBitmap>,
+    /// See `TopologyObject::complete_nodeset`.
+    pub complete_nodeset: Option<PureBitmap>,
+    /// See `TopologyObject::allowed_nodeset`.
+    pub allowed_nodeset: Option<PureBitmap>,
+    /// The attributes of the cache, only set for `ObjectType::Cache` objects.
+    pub cache: Option<CacheSnapshot>,
+    /// Snapshots of the children of this object.
+    pub children: Vec<ObjectSnapshot>,
+}
+
+/// The attributes of a cache object in an `ObjectSnapshot`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CacheSnapshot {
+    /// Size of the cache in bytes.
+    pub size: u64,
+    /// Depth of the cache, like 1 for L1.
+    pub depth: u32,
+    /// Cache line size in bytes, 0 if unknown.
+    pub line_size: u32,
+    /// The associativity of the cache.
+    pub associativity: Associativity,
+    /// Whether the cache holds data, instructions or both.
+    pub cache_type: CacheType,
+}
+
+impl ObjectSnapshot {
+    /// Takes a snapshot of `object` and its whole subtree.
+    pub fn new(object: &TopologyObject) -> ObjectSnapshot {
+        let name = object.name();
+        let cache = object.cache_attributes().map(|cache| {
+            CacheSnapshot {
+                size: cache.size(),
+                depth: cache.depth(),
+                line_size: cache.line_size(),
+                associativity: cache.associativity(),
+                cache_type: cache.cache_type(),
+            }
+        });
+
+        ObjectSnapshot {
+            object_type: object.object_type(),
+            name: if name.is_empty() { None } else { Some(name) },
+            os_index: object.os_index(),
+            depth: object.depth(),
+            logical_index: object.logical_index(),
+            local_memory: object.memory().local_memory(),
+            total_memory: object.memory().total_memory(),
+            cpuset: to_pure(object.cpuset()),
+            complete_cpuset: to_pure(object.complete_cpuset()),
+            online_cpuset: to_pure(object.online_cpuset()),
+            allowed_cpuset: to_pure(object.allowed_cpuset()),
+            nodeset: to_pure(object.nodeset()),
+            complete_nodeset: to_pure(object.complete_nodeset()),
+            allowed_nodeset: to_pure(object.allowed_nodeset()),
+            cache,
+            children: object.children().into_iter().map(ObjectSnapshot::new).collect(),
+        }
+    }
+}
+
+/// Copies a borrowed set into a `PureBitmap`.
+fn to_pure<T: AsRef<Bitmap>>(set: Option<BitmapRef<'_, T>>) -> Option<PureBitmap> {
+    set.map(|set| PureBitmap::from((*set).as_ref()))
+}
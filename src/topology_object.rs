@@ -1,11 +1,11 @@
 use libc::{c_int, c_uint, c_ulonglong, c_char, c_void, c_float, c_ushort, c_uchar};
-use std::ffi::CString;
-use std::fmt;
+use std::ffi::{CStr, CString};
+use std::{fmt, ptr};
 
 use ffi::ObjectType;
 use ffi;
 
-use bitmap::{IntHwlocBitmap, CpuSet, NodeSet};
+use bitmap::{IntHwlocBitmap, Bitmap, BitmapRef, CpuSet, NodeSet};
 
 #[repr(C)]
 pub struct TopologyObject {
@@ -62,9 +62,13 @@ impl TopologyObject {
     }
 
     /// The name of the object, if set.
+    ///
+    /// Returns an empty string if the object has no name.
     pub fn name(&self) -> String {
-        let c_str = unsafe { CString::from_raw(self.name) };
-        c_str.to_str().unwrap().to_string()
+        if self.name.is_null() {
+            return String::new();
+        }
+        unsafe { CStr::from_ptr(self.name) }.to_string_lossy().into_owned()
     }
 
     /// Vertical index in the hierarchy.
@@ -142,14 +146,133 @@ impl TopologyObject {
         self.deref_topology(&self.next_sibling)
     }
 
+    /// The first ancestor of this object with the given type.
+    ///
+    /// The object itself is not considered, so this returns `None` for the root
+    /// object or if no ancestor has the given type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    /// let pu = topology.objects_with_type(&ObjectType::PU).unwrap()[5];
+    ///
+    /// let package = pu.ancestor_of_type(&ObjectType::Package).unwrap();
+    /// assert_eq!(1, package.logical_index());
+    /// ```
+    pub fn ancestor_of_type(&self, object_type: &ObjectType) -> Option<&TopologyObject> {
+        let mut ancestor = self.parent();
+        while let Some(object) = ancestor {
+            if object.object_type() == *object_type {
+                return Some(object);
+            }
+            ancestor = object.parent();
+        }
+        None
+    }
+
+    /// The ancestor of this object at the given depth.
+    ///
+    /// Returns `None` if `depth` is not above the depth of this object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    /// let pu = topology.objects_with_type(&ObjectType::PU).unwrap()[0];
+    ///
+    /// assert_eq!(ObjectType::Machine, pu.ancestor_at_depth(0).unwrap().object_type());
+    /// assert!(pu.ancestor_at_depth(pu.depth()).is_none());
+    /// ```
+    pub fn ancestor_at_depth(&self, depth: u32) -> Option<&TopologyObject> {
+        if depth >= self.depth() {
+            return None;
+        }
+
+        let mut ancestor = self.parent();
+        while let Some(object) = ancestor {
+            if object.depth() <= depth {
+                return Some(object);
+            }
+            ancestor = object.parent();
+        }
+        None
+    }
+
+    /// The deepest object which is an ancestor of both this object and `other`.
+    ///
+    /// If one object is an ancestor of the other, that object is returned. If
+    /// both objects are the same, the object itself is returned. Returns `None`
+    /// only if the objects do not belong to the same topology.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    /// let pus = topology.objects_with_type(&ObjectType::PU).unwrap();
+    ///
+    /// // PU 0 and 1 share a core, PU 0 and 2 only share a package.
+    /// assert_eq!(ObjectType::Core, pus[0].common_ancestor(pus[1]).unwrap().object_type());
+    /// assert_eq!(ObjectType::Package, pus[0].common_ancestor(pus[2]).unwrap().object_type());
+    /// ```
+    pub fn common_ancestor<'a>(&'a self, other: &'a TopologyObject) -> Option<&'a TopologyObject> {
+        let mut left = self;
+        let mut right = other;
+
+        while !ptr::eq(left, right) {
+            while left.depth() > right.depth() {
+                left = left.parent()?;
+            }
+            while right.depth() > left.depth() {
+                right = right.parent()?;
+            }
+            if !ptr::eq(left, right) && left.depth() == right.depth() {
+                left = left.parent()?;
+                right = right.parent()?;
+            }
+        }
+        Some(left)
+    }
+
+    /// Checks whether this object is below `root` in the topology tree.
+    ///
+    /// This is decided by comparing the CPU sets of both objects, so an object
+    /// is considered to be part of its own subtree. Objects without a CPU set,
+    /// like I/O devices, are never part of a subtree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hwloc::{Topology, ObjectType};
+    ///
+    /// let topology = Topology::from_synthetic("pack:2 core:2 pu:2").unwrap();
+    /// let packages = topology.objects_with_type(&ObjectType::Package).unwrap();
+    /// let pu = topology.objects_with_type(&ObjectType::PU).unwrap()[0];
+    ///
+    /// assert!(pu.is_in_subtree(packages[0]));
+    /// assert!(!pu.is_in_subtree(packages[1]));
+    /// ```
+    pub fn is_in_subtree(&self, root: &TopologyObject) -> bool {
+        match (self.cpuset(), root.cpuset()) {
+            (Some(cpuset), Some(root_cpuset)) => cpuset.is_included_in(&root_cpuset),
+            _ => false,
+        }
+    }
+
     /// CPUs covered by this object.
     ///
     /// This is the set of CPUs for which there are PU objects in the
     /// topology under this object, i.e. which are known to be physically
     /// contained in this object and known how (the children path between this
     /// object and the PU objects).
-    pub fn cpuset(&self) -> Option<CpuSet> {
-        self.deref_cpuset(self.cpuset)
+    pub fn cpuset(&self) -> Option<BitmapRef<'_, CpuSet>> {
+        self.deref_bitmap(self.cpuset)
     }
 
     /// The complete CPU set of logical processors of this object.
@@ -160,8 +283,8 @@ impl TopologyObject {
     /// not set. Thus no corresponding PU object may be found in the topology,
     /// because the precise position is undefined. It is however known that it
     /// would be somewhere under this object.
-    pub fn complete_cpuset(&self) -> Option<CpuSet> {
-        self.deref_cpuset(self.complete_cpuset)
+    pub fn complete_cpuset(&self) -> Option<BitmapRef<'_, CpuSet>> {
+        self.deref_bitmap(self.complete_cpuset)
     }
 
     /// The CPU set of online logical processors.
@@ -169,8 +292,8 @@ impl TopologyObject {
     /// This includes the CPUs contained in this object that are online,
     /// i.e. draw power and can execute threads. It may however not be allowed
     /// to bind to them due to administration rules, see allowed_cpuset.
-    pub fn online_cpuset(&self) -> Option<CpuSet> {
-        self.deref_cpuset(self.online_cpuset)
+    pub fn online_cpuset(&self) -> Option<BitmapRef<'_, CpuSet>> {
+        self.deref_bitmap(self.online_cpuset)
     }
 
     /// The CPU set of allowed logical processors.
@@ -180,8 +303,8 @@ impl TopologyObject {
     /// return permission errors. This is usually restricted by administration
     /// rules. Some of them may however be offline so binding to them may still
     /// not be possible, see online_cpuset.
-    pub fn allowed_cpuset(&self) -> Option<CpuSet> {
-        self.deref_cpuset(self.allowed_cpuset)
+    pub fn allowed_cpuset(&self) -> Option<BitmapRef<'_, CpuSet>> {
+        self.deref_bitmap(self.allowed_cpuset)
     }
 
     /// NUMA nodes covered by this object or containing this object.
@@ -196,8 +319,8 @@ impl TopologyObject {
     ///
     /// If there are no NUMA nodes in the machine, all the memory is close to this object, so the
     /// nodeset is full.
-    pub fn nodeset(&self) -> Option<NodeSet> {
-        self.deref_nodeset(self.nodeset)
+    pub fn nodeset(&self) -> Option<BitmapRef<'_, NodeSet>> {
+        self.deref_bitmap(self.nodeset)
     }
 
     /// The complete NUMA node set of this object,.
@@ -210,8 +333,8 @@ impl TopologyObject {
     ///
     /// If there are no NUMA nodes in the machine, all the memory is close to this object, so
     /// complete_nodeset is full.
-    pub fn complete_nodeset(&self) -> Option<NodeSet> {
-        self.deref_nodeset(self.complete_nodeset)
+    pub fn complete_nodeset(&self) -> Option<BitmapRef<'_, NodeSet>> {
+        self.deref_bitmap(self.complete_nodeset)
     }
 
     /// The set of allowed NUMA memory nodes.
@@ -222,37 +345,28 @@ impl TopologyObject {
     ///
     /// If there are no NUMA nodes in the machine, all the memory is close to this object, so
     /// allowed_nodeset is full.
-    pub fn allowed_nodeset(&self) -> Option<NodeSet> {
-        self.deref_nodeset(self.allowed_nodeset)
+    pub fn allowed_nodeset(&self) -> Option<BitmapRef<'_, NodeSet>> {
+        self.deref_bitmap(self.allowed_nodeset)
     }
 
     fn deref_topology(&self, p: &*mut TopologyObject) -> Option<&TopologyObject> {
         unsafe { if p.is_null() { None } else { Some(&**p) } }
     }
 
-    fn deref_cpuset(&self, p: *mut IntHwlocBitmap) -> Option<CpuSet> {
+    fn deref_bitmap<T: From<Bitmap>>(&self, p: *mut IntHwlocBitmap) -> Option<BitmapRef<'_, T>> {
         if p.is_null() {
             None
         } else {
-            Some(CpuSet::from_raw(p, false))
-        }
-    }
-
-    fn deref_nodeset(&self, p: *mut IntHwlocBitmap) -> Option<NodeSet> {
-        if p.is_null() {
-            None
-        } else {
-            Some(NodeSet::from_raw(p, false))
+            Some(unsafe { BitmapRef::from_raw(p) })
         }
     }
 
+    /// The attributes of the cache, or `None` if this is not an `ObjectType::Cache` object.
     pub fn cache_attributes(&self) -> Option<&TopologyObjectCacheAttributes> {
-        let cache_ptr = unsafe { (*self.attr).cache() };
-        if cache_ptr.is_null() {
-            None
-        } else {
-            unsafe { Some(&*cache_ptr) }
+        if self.object_type != ObjectType::Cache || self.attr.is_null() {
+            return None;
         }
+        unsafe { Some(&*(*self.attr).cache()) }
     }
 }
 
@@ -385,28 +499,70 @@ impl TopologyObjectAttributes {
 
 #[repr(C)]
 pub struct TopologyObjectCacheAttributes {
-    pub size: c_ulonglong,
-    pub depth: c_uint,
-    pub linesize: c_uint,
-    pub associativity: c_int,
-    pub _type: TopologyObjectCacheType,
+    size: c_ulonglong,
+    depth: c_uint,
+    linesize: c_uint,
+    associativity: c_int,
+    _type: c_int,
 }
 
 impl TopologyObjectCacheAttributes {
+    /// Size of the cache in bytes.
     pub fn size(&self) -> u64 {
         self.size
     }
 
+    /// Depth of the cache, like 1 for L1.
     pub fn depth(&self) -> u32 {
         self.depth
     }
+
+    /// Cache line size in bytes, 0 if unknown.
+    pub fn line_size(&self) -> u32 {
+        self.linesize
+    }
+
+    /// The associativity of the cache.
+    pub fn associativity(&self) -> Associativity {
+        match self.associativity {
+            -1 => Associativity::Full,
+            n if n > 0 => Associativity::Ways(n as u32),
+            _ => Associativity::Unknown,
+        }
+    }
+
+    /// Whether the cache holds data, instructions or both.
+    pub fn cache_type(&self) -> CacheType {
+        match self._type {
+            1 => CacheType::Data,
+            2 => CacheType::Instruction,
+            _ => CacheType::Unified,
+        }
+    }
 }
 
-#[repr(C)]
-pub enum TopologyObjectCacheType {
-    Unified = 0,
-    Data = 1,
-    Instruction = 2,
+/// The associativity of a cache, see `TopologyObjectCacheAttributes::associativity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Associativity {
+    /// The cache is fully associative.
+    Full,
+    /// The cache is set associative with the given number of ways.
+    Ways(u32),
+    /// The associativity is unknown.
+    Unknown,
+}
+
+/// The type of a cache, see `TopologyObjectCacheAttributes::cache_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CacheType {
+    /// Unified cache holding both data and instructions.
+    Unified,
+    /// Data cache.
+    Data,
+    /// Instruction cache.
+    Instruction,
 }
 
 #[repr(C)]